@@ -0,0 +1,25 @@
+use std::fs;
+
+/// Exposes the resolved `niri-ipc` version as `NIRI_IPC_VERSION`, so that it can be
+/// reported by `--version`.
+fn main() {
+    println!("cargo:rerun-if-changed=Cargo.lock");
+
+    let version = fs::read_to_string("Cargo.lock")
+        .ok()
+        .and_then(|lock| lock.parse::<toml::Table>().ok())
+        .and_then(|lock| {
+            lock.get("package")?
+                .as_array()?
+                .iter()
+                .find(|package| {
+                    package.get("name").and_then(|name| name.as_str()) == Some("niri-ipc")
+                })?
+                .get("version")?
+                .as_str()
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+
+    println!("cargo:rustc-env=NIRI_IPC_VERSION={version}");
+}
@@ -1,369 +1,2448 @@
 use std::{
-    cmp::Ordering,
-    collections::HashSet,
-    path::PathBuf,
-    process::{Command, Stdio},
+    env, io, iter,
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use directories::UserDirs;
-use miette::{Context, IntoDiagnostic, Result, bail, miette};
+use miette::{Context, IntoDiagnostic, Report, Result, bail, miette};
+use niri_ipc::Window;
 use niri_ipc::{Action, Request, Response, Workspace, WorkspaceReferenceArg};
-use niri_ipc::{Window, socket::Socket};
 
-use crate::config::{Application, MatchRule};
+use crate::{
+    config::{Application, MultiPolicy, OnFocused, Settings, SpawnScope},
+    error::AppError,
+    ipc::NiriIpc,
+    matching::{match_application_windows, select_application_window},
+    profile,
+};
+
+/// Replaces a leading `~` component with the home directory. `~user` paths are not
+/// supported and are rejected rather than passed through unexpanded.
+pub fn expand_home(path: PathBuf) -> Result<PathBuf> {
+    let dirs = UserDirs::new();
+    expand_home_with(path, dirs.as_ref().map(UserDirs::home_dir))
+}
+
+fn expand_home_with(path: PathBuf, home_dir: Option<&Path>) -> Result<PathBuf> {
+    let Some(first) = path.components().next() else {
+        return Ok(path);
+    };
+    let first = first.as_os_str().to_string_lossy();
+    if first == "~" {
+        let home_dir = home_dir
+            .ok_or_else(|| miette!("Could not determine home directory to expand {path:?}"))?;
+        let suffix = path.strip_prefix("~").into_diagnostic()?;
+        return Ok(home_dir.join(suffix));
+    }
+    if first.starts_with('~') {
+        bail!("Expanding other users' home directories is not supported: {path:?}");
+    }
+
+    Ok(path)
+}
+
+/// Replaces `$VAR` and `${VAR}` with the value of the environment variable, and `$$` with a
+/// literal `$`. A `$` that is not followed by a variable name is kept as is. Fails if a
+/// referenced variable is not set.
+fn expand_env_vars(arg: &str) -> Result<String> {
+    expand_env_vars_with(arg, |name| env::var(name).ok())
+}
+
+fn expand_env_vars_with(arg: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let is_name_char = |c: char| c.is_ascii_alphanumeric() || c == '_';
 
-fn expand_home(path: PathBuf) -> PathBuf {
-    if let Ok(suffix) = path.strip_prefix("~") {
-        if let Some(dirs) = UserDirs::new() {
-            return dirs.home_dir().join(suffix);
+    let mut expanded = String::with_capacity(arg.len());
+    let mut rest = arg;
+    while let Some(position) = rest.find('$') {
+        expanded.push_str(&rest[..position]);
+        rest = &rest[position + 1..];
+
+        let (name, remainder) = if let Some(braced) = rest.strip_prefix('{') {
+            let end = braced
+                .find('}')
+                .ok_or_else(|| miette!("Unterminated `${{` in spawn argument {arg:?}"))?;
+            (&braced[..end], &braced[end + 1..])
+        } else if let Some(remainder) = rest.strip_prefix('$') {
+            expanded.push('$');
+            rest = remainder;
+            continue;
+        } else {
+            let end = rest.find(|c| !is_name_char(c)).unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        };
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+            expanded.push('$');
+            continue;
         }
+
+        let value = lookup(name).ok_or_else(|| {
+            miette!("Environment variable `{name}` used in spawn argument {arg:?} is not set")
+        })?;
+        expanded.push_str(&value);
+        rest = remainder;
     }
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+/// Polling for windows starts fast, so that quickly appearing windows are picked up with
+/// little delay, and doubles the interval up to the maximum, so that slow launches do not
+/// flood Niri with requests.
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(25);
+const MAX_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+const SYSTEMD_RUN: &str = "systemd-run";
+
+/// How long [`launch`] waits for the process to exit right away, e.g. because the shell of
+/// a `spawn-sh` command could not find the program. A process still running by then counts
+/// as launched.
+const EARLY_EXIT_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Checks whether an executable with the given name exists in any directory of `$PATH`.
+fn is_in_path(program: &str) -> bool {
+    env::var_os("PATH").is_some_and(|paths| {
+        env::split_paths(&paths).any(|dir| Path::new(&dir).join(program).is_file())
+    })
+}
+
+const PLACEHOLDERS: [&str; 2] = ["{window_id}", "{app_id}"];
+
+/// Substitutes the `{window_id}` and `{app_id}` placeholders with the properties of
+/// `window`. Without a window, or when the window has no app ID, they expand to an empty
+/// string. Anything else in braces is left untouched. With `for_shell`, the values are
+/// quoted, as windows pick their own app ID and must not be able to inject commands.
+fn expand_placeholders(arg: &str, window: Option<&Window>, for_shell: bool) -> String {
+    let window_id = window.map(|window| window.id.to_string());
+    let app_id = window.and_then(|window| window.app_id.as_deref());
+    let value = |value: Option<&str>| {
+        let value = value.unwrap_or_default();
+        if for_shell {
+            shell_quote(value)
+        } else {
+            value.to_string()
+        }
+    };
+
+    arg.replace("{window_id}", &value(window_id.as_deref()))
+        .replace("{app_id}", &value(app_id))
+}
+
+/// Checks whether the spawn command of the application references the matched window.
+pub fn uses_placeholders(application: &Application) -> bool {
+    application
+        .spawn
+        .iter()
+        .flatten()
+        .chain(&application.spawn_sh)
+        .any(|arg| {
+            PLACEHOLDERS
+                .iter()
+                .any(|placeholder| arg.contains(placeholder))
+        })
+}
+
+/// Returns the window currently targeted by the application's rules, if any.
+pub fn find_matched_window(
+    socket: &mut impl NiriIpc,
+    application: &Application,
+    settings: &Settings,
+) -> Result<Option<Window>> {
+    let (windows, _) = get_window_and_workspace_list(socket)?;
+    let window_id = select_application_window(&windows, application, settings)?;
+
+    Ok(window_id.and_then(|window_id| windows.into_iter().find(|window| window.id == window_id)))
+}
 
-    path
+/// Checks whether the application's rules match any open window.
+pub fn has_matched_windows(socket: &mut impl NiriIpc, application: &Application) -> Result<bool> {
+    let (windows, _) = get_window_and_workspace_list(socket)?;
+
+    Ok(!match_application_windows(&windows, application).is_empty())
 }
 
-pub fn launch(application: &Application) -> Result<()> {
-    let command: PathBuf;
-    let args: Vec<String>;
-    if let Some(spawn_command) = &application.spawn {
-        let mut iter = spawn_command.iter();
-        command = iter
+/// Returns the program and arguments that launch the application, wrapped according to its
+/// `spawn-scope`. A `spawn-sh` command is passed to the shell as a single argument, so
+/// multiline scripts are kept intact.
+fn command_line(
+    application: &Application,
+    settings: &Settings,
+    window: Option<&Window>,
+) -> Result<(PathBuf, Vec<String>)> {
+    let (command, mut args) = if let Some(spawn_command) = &application.spawn {
+        let mut iter = spawn_command
+            .iter()
+            .map(|arg| Ok(expand_placeholders(&expand_env_vars(arg)?, window, false)))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter();
+        let program = iter
             .next()
-            .map(PathBuf::from)
-            .map(expand_home)
-            .ok_or_else(|| miette!("Spawn command is empty"))?;
-        args = iter.cloned().collect();
+            .ok_or_else(|| AppError::ConfigInvalid("Spawn command is empty".to_string()))?;
+        (expand_home(PathBuf::from(program))?, iter.collect())
     } else if let Some(spawn_sh_command) = &application.spawn_sh {
-        command = PathBuf::from("sh");
-        args = Vec::from(["-c".to_string(), spawn_sh_command.clone()]);
+        (
+            PathBuf::from(settings.shell()),
+            Vec::from([
+                "-c".to_string(),
+                expand_placeholders(spawn_sh_command, window, true),
+            ]),
+        )
     } else {
-        bail!(
+        bail!(AppError::ConfigInvalid(format!(
             "No spawn command or spawn_sh command specified for application {}",
             application.name
+        )));
+    };
+
+    if application.spawn_scope == SpawnScope::Systemd {
+        if is_in_path(SYSTEMD_RUN) {
+            let mut scoped_args = Vec::from([
+                "--user".to_string(),
+                "--scope".to_string(),
+                "--".to_string(),
+                command.to_string_lossy().into_owned(),
+            ]);
+            scoped_args.append(&mut args);
+            return Ok((PathBuf::from(SYSTEMD_RUN), scoped_args));
+        }
+        eprintln!(
+            "Warning: {SYSTEMD_RUN} not found, spawning application '{}' without a scope",
+            application.name
         );
+    }
+
+    Ok((command, args))
+}
+
+/// Quotes an argument for a POSIX shell, unless it consists of characters that are safe
+/// unquoted.
+fn shell_quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-+=/:.,@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Returns the command line that [`launch`] would run, quoted so that it can be pasted
+/// into a shell.
+pub fn format_command_line(
+    application: &Application,
+    settings: &Settings,
+    window: Option<&Window>,
+) -> Result<String> {
+    let (command, args) = command_line(application, settings, window)?;
+
+    Ok(iter::once(command.to_string_lossy().into_owned())
+        .chain(args)
+        .map(|arg| shell_quote(&arg))
+        .collect::<Vec<_>>()
+        .join(" "))
+}
+
+/// Spawns the application detached from this process. Environment variables in `spawn`
+/// arguments are expanded, and the `{window_id}` and `{app_id}` placeholders in the spawn
+/// command are filled in from `window`.
+pub fn launch(
+    application: &Application,
+    settings: &Settings,
+    window: Option<&Window>,
+) -> Result<()> {
+    let (command, args) = command_line(application, settings, window)?;
+    let program = command.clone();
+
+    let stdio = || {
+        if settings.inherit_io() {
+            Stdio::inherit()
+        } else {
+            Stdio::null()
+        }
     };
 
     let mut process = Command::new(command);
     let process = process
         .args(args)
         .stdin(Stdio::null())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null());
-
-    let mut child = process
-        .spawn()
-        .into_diagnostic()
-        .context("Failed to spawn process")?;
-    child
-        .wait()
-        .into_diagnostic()
-        .context("Failed to wait for spawned process")?;
+        .stdout(stdio())
+        .stderr(stdio());
 
-    Ok(())
-}
+    // SAFETY: `setsid` is async-signal-safe, so it may be called between fork and exec.
+    unsafe {
+        // Start a new session so the application is detached from our terminal and is not
+        // killed along with it.
+        process.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
 
-fn is_window_match_rule(window: &Window, rule: &MatchRule) -> bool {
-    if let Some(app_id_re) = &rule.app_id {
-        let Some(app_id) = &window.app_id else {
-            return false;
-        };
-        if !app_id_re.0.is_match(app_id) {
-            return false;
+    let mut child = match process.spawn() {
+        Ok(child) => child,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => bail!(
+            "Command {program:?} of application '{}' not found",
+            application.name
+        ),
+        Err(err) => {
+            return Err(err)
+                .into_diagnostic()
+                .context("Failed to spawn process");
         }
+    };
+
+    if let Some(status) = wait_with_timeout(&mut child, EARLY_EXIT_TIMEOUT)?
+        && !status.success()
+    {
+        bail!(
+            "Application '{}' exited right after launching with {status}",
+            application.name
+        );
     }
 
-    if let Some(title_re) = &rule.title {
-        let Some(title) = &window.title else {
-            return false;
-        };
-        if !title_re.0.is_match(title) {
-            return false;
+    // Reap the process in the background instead of blocking until the application exits.
+    thread::spawn(move || child.wait());
+
+    Ok(())
+}
+
+/// Waits up to `timeout` for the process to exit and returns its exit status, or `None` if
+/// it is still running.
+fn wait_with_timeout(child: &mut Child, timeout: Duration) -> Result<Option<ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let status = child
+            .try_wait()
+            .into_diagnostic()
+            .context("Failed to check whether the process exited")?;
+        let now = Instant::now();
+        if status.is_some() || now >= deadline {
+            return Ok(status);
         }
+        thread::sleep((deadline - now).min(Duration::from_millis(5)));
     }
-
-    true
 }
 
-fn match_windows_with_rules<'a>(
-    windows: &'a Vec<Window>,
-    rules: &'a Vec<MatchRule>,
-) -> Vec<(Option<usize>, Vec<u64>)> {
-    let mut mappings = vec![];
-    for rule in rules {
-        let mut matched_windows = windows
-            .iter()
-            .filter(|window| is_window_match_rule(window, rule))
-            .collect::<Vec<_>>();
-        matched_windows.sort_by(|a, b| match (a.pid, b.pid) {
-            (Some(a_pid), Some(b_pid)) => a_pid.cmp(&b_pid),
-            _ => Ordering::Equal,
-        });
+/// Launches the application, then waits up to `timeout` for a matching window to appear
+/// and focuses it.
+pub fn launch_and_focus(
+    socket: &mut impl NiriIpc,
+    application: &Application,
+    settings: &Settings,
+    timeout: Duration,
+    window: Option<&Window>,
+) -> Result<()> {
+    launch(application, settings, window)?;
 
-        mappings.push((
-            rule.index,
-            matched_windows.iter().map(|window| window.id).collect(),
-        ));
-    }
+    let Some(window_id) = wait_for_matched_window(socket, application, settings, timeout)? else {
+        eprintln!(
+            "Warning: no window matching application '{}' appeared within {}ms",
+            application.name,
+            timeout.as_millis()
+        );
+        return Ok(());
+    };
 
-    mappings
+    focus_window(socket, window_id, settings)?;
+
+    Ok(())
 }
 
-fn get_matched_window_and_workspace<'a>(
-    windows: &'a Vec<Window>,
-    workspaces: &'a Vec<Workspace>,
-    matches: &'a Vec<MatchRule>,
-    excludes: &'a Vec<MatchRule>,
-) -> Result<Option<(&'a Window, &'a Workspace)>> {
-    // Collect excluded window IDs
-    let mut excluded_window_ids = HashSet::new();
-    for (rule_index, matched_window_ids) in match_windows_with_rules(windows, excludes) {
-        for (index, window_id) in matched_window_ids.iter().enumerate() {
-            match rule_index {
-                Some(rule_index) if rule_index == index => {
-                    excluded_window_ids.insert(*window_id);
-                }
-                None => {
-                    excluded_window_ids.insert(*window_id);
-                }
-                _ => {}
-            }
-        }
-    }
+fn wait_for_matched_window(
+    socket: &mut impl NiriIpc,
+    application: &Application,
+    settings: &Settings,
+    timeout: Duration,
+) -> Result<Option<u64>> {
+    let (windows, _) = poll_window_and_workspace_list(socket, application, settings, timeout)?;
 
-    // Collect included window IDs
-    let mut include_window_ids = HashSet::new();
-    for (rule_index, matched_window_ids) in match_windows_with_rules(&windows, matches) {
-        for (index, window_id) in matched_window_ids.iter().enumerate() {
-            match rule_index {
-                Some(rule_index) if rule_index == index => {
-                    include_window_ids.insert(*window_id);
-                }
-                None => {
-                    include_window_ids.insert(*window_id);
-                }
-                _ => {}
-            }
+    select_application_window(&windows, application, settings)
+}
+
+/// Fetches the window and workspace lists until the application's rules match a window or
+/// `timeout` expires, and returns the last lists fetched.
+fn poll_window_and_workspace_list(
+    socket: &mut impl NiriIpc,
+    application: &Application,
+    settings: &Settings,
+    timeout: Duration,
+) -> Result<(Vec<Window>, Vec<Workspace>)> {
+    let start = Instant::now();
+    let mut interval = MIN_POLL_INTERVAL;
+    loop {
+        let (windows, workspaces) = get_window_and_workspace_list(socket)?;
+        let matched = select_application_window(&windows, application, settings)?.is_some();
+        let remaining = timeout.saturating_sub(start.elapsed());
+        if matched || remaining.is_zero() {
+            return Ok((windows, workspaces));
         }
+
+        thread::sleep(with_jitter(interval).min(remaining));
+        interval = next_poll_interval(interval);
     }
+}
 
-    // Final matched windows after applying excludes and includes
-    let matched_windows = windows
-        .iter()
-        .filter(|window| {
-            !excluded_window_ids.contains(&window.id) && include_window_ids.contains(&window.id)
-        })
-        .collect::<Vec<_>>();
+fn next_poll_interval(interval: Duration) -> Duration {
+    (interval * 2).min(MAX_POLL_INTERVAL)
+}
 
-    // Check results
-    if matched_windows.len() > 1 {
-        bail!(
-            "Multiple windows matched the given rules. Refine your match/exclude rules to target a single window. Matched windows: {:#?}",
-            matched_windows
-        );
+/// Adds up to a quarter of `interval`, so that several processes waiting at the same time
+/// do not query Niri in lockstep.
+fn with_jitter(interval: Duration) -> Duration {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.subsec_nanos());
+
+    interval + interval / 4 * (nanos % 100) / 100
+}
+
+/// Fetches the window and workspace lists for acting on the application. With the
+/// `rematch-timeout-ms` setting, briefly keeps re-fetching while nothing matches, to ride
+/// out windows whose titles change transiently.
+fn get_window_and_workspace_list_for(
+    socket: &mut impl NiriIpc,
+    application: &Application,
+    settings: &Settings,
+) -> Result<(Vec<Window>, Vec<Workspace>)> {
+    match settings.rematch_timeout() {
+        Some(timeout) => poll_window_and_workspace_list(socket, application, settings, timeout),
+        None => get_window_and_workspace_list(socket),
     }
+}
 
-    if matched_windows.is_empty() {
+fn get_matched_window_and_workspace<'a>(
+    windows: &'a [Window],
+    workspaces: &'a [Workspace],
+    application: &Application,
+    settings: &Settings,
+) -> Result<Option<(&'a Window, &'a Workspace)>> {
+    let Some(matched_window) = select_application_window(windows, application, settings)?
+        .and_then(|window_id| windows.iter().find(|window| window.id == window_id))
+    else {
         return Ok(None);
-    }
+    };
 
     // Get workspace of matched window
-    let matched_window = matched_windows[0];
     let matched_window_workspace_id = matched_window.workspace_id.ok_or_else(|| {
-        miette!(
+        AppError::IpcFailed(format!(
             "Matched window with id {} does not belong to any workspace",
             matched_window.id
-        )
+        ))
     })?;
     let matched_window_workspace = workspaces
         .iter()
         .find(|workspace| workspace.id == matched_window_workspace_id)
         .ok_or_else(|| {
-            miette!(
+            AppError::IpcFailed(format!(
                 "Workspace with id {} not found for matched window",
                 matched_window_workspace_id
-            )
+            ))
         })?;
 
     Ok(Some((matched_window, matched_window_workspace)))
 }
 
-fn get_focused_window(windows: &Vec<Window>) -> Option<&Window> {
+fn get_focused_window(windows: &[Window]) -> Option<&Window> {
     windows.iter().find(|window| window.is_focused)
 }
 
-fn get_focused_workspace(workspaces: &Vec<Workspace>) -> Result<&Workspace> {
+fn get_focused_workspace(workspaces: &[Workspace]) -> Result<&Workspace> {
     workspaces
         .iter()
         .find(|workspace| workspace.is_focused)
-        .ok_or_else(|| miette!("No focused workspace found"))
+        .ok_or_else(|| AppError::IpcFailed("No focused workspace found".to_string()).into())
 }
 
-fn get_hidden_workspace(workspaces: &Vec<Workspace>) -> Result<&Workspace> {
-    workspaces
+/// Returns the workspace currently shown on the named output, unless it is a hidden one.
+fn get_output_workspace<'a>(
+    workspaces: &'a [Workspace],
+    output: &str,
+    settings: &Settings,
+) -> Result<&'a Workspace> {
+    let on_output = |workspace: &&Workspace| workspace.output.as_deref() == Some(output);
+    if let Some(workspace) = workspaces
+        .iter()
+        .filter(on_output)
+        .find(|workspace| workspace.is_active && !is_hidden_workspace(workspace, settings))
+    {
+        return Ok(workspace);
+    }
+
+    if workspaces.iter().any(|workspace| on_output(&workspace)) {
+        bail!("No active workspace found on output '{output}'");
+    }
+    let mut outputs = workspaces
+        .iter()
+        .filter_map(|workspace| workspace.output.as_deref())
+        .collect::<Vec<_>>();
+    outputs.sort_unstable();
+    outputs.dedup();
+    bail!(
+        "Unknown output '{output}', available outputs: {}",
+        outputs.join(", ")
+    );
+}
+
+/// Looks up a workspace by its name, or else by its ID.
+fn find_workspace<'a>(workspaces: &'a [Workspace], reference: &str) -> Result<&'a Workspace> {
+    let by_name = workspaces
+        .iter()
+        .find(|workspace| workspace.name.as_deref() == Some(reference));
+    let by_id = || {
+        let id = reference.parse::<u64>().ok()?;
+        workspaces.iter().find(|workspace| workspace.id == id)
+    };
+    if let Some(workspace) = by_name.or_else(by_id) {
+        return Ok(workspace);
+    }
+
+    let available = workspaces
+        .iter()
+        .map(|workspace| {
+            workspace
+                .name
+                .clone()
+                .unwrap_or_else(|| workspace.id.to_string())
+        })
+        .collect::<Vec<_>>();
+    bail!(
+        "Unknown workspace '{reference}', available workspaces: {}",
+        available.join(", ")
+    );
+}
+
+/// Returns the workspace hidden windows are moved to: one named after the `hidden-workspace`
+/// pattern, or else one flagged as hidden by Niri. Of several, the one on the focused output
+/// is taken, so that windows are hidden on the monitor they are shown on.
+fn get_hidden_workspace<'a>(
+    workspaces: &'a [Workspace],
+    settings: &Settings,
+) -> Result<&'a Workspace> {
+    let focused_output = workspaces
         .iter()
-        .find(|workspace| workspace.is_hidden)
-        .ok_or_else(|| miette!("No hidden workspace found"))
-}
-
-fn get_window_and_workspace_list(socket: &mut Socket) -> Result<(Vec<Window>, Vec<Workspace>)> {
-    let (Ok(Response::Windows(windows)), Ok(Response::Workspaces(workspaces))) = (
-        socket.send(Request::Windows).into_diagnostic()?,
-        socket
-            .send(Request::WorkspacesWithHidden)
-            .into_diagnostic()?,
-    ) else {
-        bail!("Failed to retrieve windows or workspaces from Niri daemon");
+        .find(|workspace| workspace.is_focused)
+        .and_then(|workspace| workspace.output.as_deref());
+    let pick = |is_candidate: &dyn Fn(&Workspace) -> bool| {
+        let candidates = workspaces
+            .iter()
+            .filter(|workspace| is_candidate(workspace))
+            .collect::<Vec<_>>();
+        candidates
+            .iter()
+            .find(|workspace| {
+                focused_output.is_some() && workspace.output.as_deref() == focused_output
+            })
+            .or(candidates.first())
+            .copied()
     };
 
-    Ok((windows, workspaces))
+    let named_workspace = settings.hidden_workspace.as_ref().and_then(|pattern| {
+        pick(&|workspace| {
+            workspace
+                .name
+                .as_ref()
+                .is_some_and(|name| pattern.is_match(name))
+        })
+    });
+
+    named_workspace
+        .or_else(|| pick(&|workspace| workspace.is_hidden))
+        .ok_or_else(|| AppError::NoHiddenWorkspace.into())
 }
 
-pub fn show(application: &Application) -> Result<()> {
-    let mut socket = Socket::connect().into_diagnostic()?;
-    let (windows, workspaces) = get_window_and_workspace_list(&mut socket)?;
+/// Fails when `workspace` is the hidden workspace, e.g. because the user switched to it.
+/// A window moved there and focused would stay invisible.
+fn ensure_not_hidden(
+    workspace: &Workspace,
+    settings: &Settings,
+    action: &'static str,
+) -> Result<()> {
+    if is_hidden_workspace(workspace, settings) {
+        bail!(AppError::HiddenWorkspaceFocused(action));
+    }
 
-    let (matched_window, matched_window_workspace) = get_matched_window_and_workspace(
-        &windows,
-        &workspaces,
-        &application.matches,
-        &application.excludes,
-    )?
-    .ok_or_else(|| miette!("No window matched the given rules."))?;
+    Ok(())
+}
 
-    let focused_workspace = get_focused_workspace(&workspaces)?;
-    if focused_workspace.id != matched_window_workspace.id {
-        // Move the matched window to focused workspace and focus it
-        let _ = socket
-            .send(Request::Action(Action::MoveWindowToWorkspace {
-                window_id: Some(matched_window.id),
-                reference: WorkspaceReferenceArg::Id(focused_workspace.id),
-                focus: true,
-            }))
-            .into_diagnostic()?;
+/// Checks whether the workspace is flagged as hidden by Niri or is a configured hidden
+/// workspace, on any output.
+fn is_hidden_workspace(workspace: &Workspace, settings: &Settings) -> bool {
+    workspace.is_hidden
+        || settings
+            .hidden_workspace
+            .as_ref()
+            .zip(workspace.name.as_ref())
+            .is_some_and(|(pattern, name)| pattern.is_match(name))
+}
+
+/// Returns the most recently focused window on the given workspace, other than `window_id`.
+/// Without focus timestamps from Niri, any other window on the workspace is returned.
+fn get_previous_window(windows: &[Window], workspace_id: u64, window_id: u64) -> Option<&Window> {
+    windows
+        .iter()
+        .filter(|window| window.id != window_id && window.workspace_id == Some(workspace_id))
+        .max_by_key(|window| {
+            window
+                .focus_timestamp
+                .map(|timestamp| (timestamp.secs, timestamp.nanos))
+        })
+}
+
+/// Makes the window floating again after it was moved, if it was floating before and the
+/// `preserve-floating` setting is enabled.
+fn restore_floating(socket: &mut impl NiriIpc, window: &Window, settings: &Settings) -> Result<()> {
+    if settings.preserve_floating() && window.is_floating {
+        socket.send(Request::Action(Action::MoveWindowToFloating {
+            id: Some(window.id),
+        }))?;
+    }
+
+    Ok(())
+}
+
+/// Fetches the current state of a window from Niri, `None` if it no longer exists.
+fn fetch_window(socket: &mut impl NiriIpc, window_id: u64) -> Result<Option<Window>> {
+    let Response::Windows(windows) = socket.send(Request::Windows)? else {
+        bail!(AppError::IpcFailed(
+            "Failed to retrieve windows from Niri daemon".to_string()
+        ));
     };
 
-    // Matched window is already in focused workspace, just focus it
-    let _ = socket
-        .send(Request::Action(Action::FocusWindow {
-            id: matched_window.id,
-        }))
-        .into_diagnostic()?;
+    Ok(windows.into_iter().find(|window| window.id == window_id))
+}
+
+fn is_window_focused(socket: &mut impl NiriIpc, window_id: u64) -> Result<bool> {
+    Ok(fetch_window(socket, window_id)?.is_some_and(|window| window.is_focused))
+}
+
+/// Fails unless Niri reports the window as focused, for the `verify` setting.
+fn verify_focused(socket: &mut impl NiriIpc, window_id: u64) -> Result<()> {
+    if !is_window_focused(socket, window_id)? {
+        bail!("Verification failed: window {window_id} is not focused");
+    }
 
     Ok(())
 }
 
-pub fn hide(application: &Application) -> Result<()> {
-    let mut socket = Socket::connect().into_diagnostic()?;
-    let (windows, workspaces) = get_window_and_workspace_list(&mut socket)?;
+/// Fails unless Niri reports the window on the workspace, for the `verify` setting.
+fn verify_on_workspace(socket: &mut impl NiriIpc, window_id: u64, workspace_id: u64) -> Result<()> {
+    let window = fetch_window(socket, window_id)?;
+    if window.and_then(|window| window.workspace_id) != Some(workspace_id) {
+        bail!("Verification failed: window {window_id} is not on workspace {workspace_id}");
+    }
 
-    let (matched_window, matched_window_workspace) = get_matched_window_and_workspace(
-        &windows,
-        &workspaces,
-        &application.matches,
-        &application.excludes,
-    )?
-    .ok_or_else(|| miette!("No window matched the given rules."))?;
+    Ok(())
+}
 
-    let focused_window =
-        get_focused_window(&windows).ok_or_else(|| miette!("No focused window found"))?;
-    if focused_window.id != matched_window.id {
-        bail!("The matched window is not focused, cannot hide it.");
+/// Focuses the window. With the `verify-focus` setting, then asks Niri whether the window
+/// is actually focused and sends the focus request once more if it is not.
+fn focus_window(socket: &mut impl NiriIpc, window_id: u64, settings: &Settings) -> Result<()> {
+    let focus = Request::Action(Action::FocusWindow { id: window_id });
+    socket.send(focus.clone())?;
+    if !settings.verify_focus() || is_window_focused(socket, window_id)? {
+        return Ok(());
     }
 
-    let hidden_workspace = get_hidden_workspace(&workspaces)?;
-    if hidden_workspace.id == matched_window_workspace.id {
-        bail!("The matched window is already in the hidden workspace.");
+    socket.send(focus)?;
+    if !is_window_focused(socket, window_id)? {
+        eprintln!("Warning: window {window_id} did not take focus");
     }
 
-    // Move focused window to hidden workspace
-    let _ = socket
-        .send(Request::Action(Action::MoveWindowToWorkspace {
+    Ok(())
+}
+
+/// Where focus goes after a window was hidden.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AfterHide<'a> {
+    /// Wherever Niri moves it.
+    #[default]
+    Default,
+    /// To the window that was focused before the hidden one on its workspace.
+    FocusPreviousWindow,
+    /// To the workspace with this name or ID.
+    FocusWorkspace(&'a str),
+    /// Back to the previously focused workspace, if the hidden window was the only one on
+    /// its workspace.
+    PreviousWorkspace,
+}
+
+/// Moves the window to the hidden workspace, then moves focus as `after_hide` says. The
+/// workspace to focus is looked up beforehand, so that an unknown one fails before the
+/// window is moved.
+fn hide_window(
+    socket: &mut impl NiriIpc,
+    windows: &[Window],
+    workspaces: &[Workspace],
+    window: &Window,
+    hidden_workspace: &Workspace,
+    settings: &Settings,
+    after_hide: AfterHide,
+) -> Result<()> {
+    let focus_workspace = match after_hide {
+        AfterHide::FocusWorkspace(reference) => Some(find_workspace(workspaces, reference)?),
+        _ => None,
+    };
+
+    socket.send(Request::Action(Action::MoveWindowToWorkspace {
+        window_id: Some(window.id),
+        reference: WorkspaceReferenceArg::Id(hidden_workspace.id),
+        focus: false,
+    }))?;
+    restore_floating(socket, window, settings)?;
+
+    match after_hide {
+        AfterHide::Default => {}
+        AfterHide::FocusPreviousWindow => {
+            if let Some(workspace_id) = window.workspace_id
+                && let Some(previous_window) = get_previous_window(windows, workspace_id, window.id)
+            {
+                focus_window(socket, previous_window.id, settings)?;
+            }
+        }
+        AfterHide::FocusWorkspace(_) => {
+            if let Some(workspace) = focus_workspace {
+                socket.send(Request::Action(Action::FocusWorkspace {
+                    reference: WorkspaceReferenceArg::Id(workspace.id),
+                }))?;
+            }
+        }
+        AfterHide::PreviousWorkspace => {
+            // Niri keeps the workspace history, so the CLI does not have to
+            let workspace_left_empty = !windows
+                .iter()
+                .any(|other| other.id != window.id && other.workspace_id == window.workspace_id);
+            if workspace_left_empty {
+                socket.send(Request::Action(Action::FocusWorkspacePrevious {}))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn get_window_and_workspace_list(
+    socket: &mut impl NiriIpc,
+) -> Result<(Vec<Window>, Vec<Workspace>)> {
+    profile::time("fetch windows and workspaces", || {
+        let (Response::Windows(windows), Response::Workspaces(workspaces)) = (
+            socket.send(Request::Windows)?,
+            socket.send(Request::WorkspacesWithHidden)?,
+        ) else {
+            bail!(AppError::IpcFailed(
+                "Failed to retrieve windows or workspaces from Niri daemon".to_string()
+            ));
+        };
+
+        Ok((windows, workspaces))
+    })
+}
+
+/// Brings the matched window to the focused workspace, or to the active workspace of
+/// `output`, and focuses it. Without `focus`, the window is moved but the current focus is
+/// kept.
+pub fn show(
+    socket: &mut impl NiriIpc,
+    application: &Application,
+    settings: &Settings,
+    focus: bool,
+    output: Option<&str>,
+) -> Result<()> {
+    let (windows, workspaces) = get_window_and_workspace_list_for(socket, application, settings)?;
+
+    let (matched_window, matched_window_workspace) =
+        get_matched_window_and_workspace(&windows, &workspaces, application, settings)?
+            .ok_or(AppError::NoMatch)?;
+
+    let target_workspace = match output {
+        Some(output) => get_output_workspace(&workspaces, output, settings)?,
+        None => get_focused_workspace(&workspaces)?,
+    };
+    ensure_not_hidden(target_workspace, settings, "show")?;
+    if target_workspace.id != matched_window_workspace.id {
+        // Move the matched window to target workspace
+        socket.send(Request::Action(Action::MoveWindowToWorkspace {
             window_id: Some(matched_window.id),
-            reference: WorkspaceReferenceArg::Id(hidden_workspace.id),
-            focus: false,
-        }))
-        .into_diagnostic()?;
+            reference: WorkspaceReferenceArg::Id(target_workspace.id),
+            focus,
+        }))?;
+        restore_floating(socket, matched_window, settings)?;
+    };
+
+    if focus {
+        // Matched window is in target workspace now, focus it
+        focus_window(socket, matched_window.id, settings)?;
+    }
+
+    if settings.verify() {
+        verify_on_workspace(socket, matched_window.id, target_workspace.id)?;
+        if focus {
+            verify_focused(socket, matched_window.id)?;
+        }
+    }
 
     Ok(())
 }
 
-pub fn activate(application: &Application) -> Result<()> {
-    let mut socket = Socket::connect().into_diagnostic()?;
-    let (windows, workspaces) = get_window_and_workspace_list(&mut socket)?;
+/// Moves the focused matched window to the hidden workspace. Focus then moves as
+/// `after_hide` says.
+pub fn hide(
+    socket: &mut impl NiriIpc,
+    application: &Application,
+    settings: &Settings,
+    after_hide: AfterHide,
+) -> Result<()> {
+    let (windows, workspaces) = get_window_and_workspace_list_for(socket, application, settings)?;
+
+    let (matched_window, matched_window_workspace) =
+        get_matched_window_and_workspace(&windows, &workspaces, application, settings)?
+            .ok_or(AppError::NoMatch)?;
 
-    let (matched_window, matched_window_workspace) = get_matched_window_and_workspace(
+    let focused_window = get_focused_window(&windows).ok_or(AppError::NotFocused("hide"))?;
+    if focused_window.id != matched_window.id {
+        bail!(AppError::NotFocused("hide"));
+    }
+
+    let hidden_workspace = get_hidden_workspace(&workspaces, settings)?;
+    if hidden_workspace.id == matched_window_workspace.id {
+        bail!(AppError::AlreadyHidden);
+    }
+
+    // Move focused window to hidden workspace
+    hide_window(
+        socket,
         &windows,
         &workspaces,
-        &application.matches,
-        &application.excludes,
-    )?
-    .ok_or_else(|| miette!("No window matched the given rules."))?;
+        matched_window,
+        hidden_workspace,
+        settings,
+        after_hide,
+    )
+}
+
+/// Focuses the matched window wherever it is, switching to its workspace first if needed.
+/// Unlike the commands that bring an application up, it never launches the application,
+/// and fails if it has no window.
+pub fn focus(
+    socket: &mut impl NiriIpc,
+    application: &Application,
+    settings: &Settings,
+) -> Result<()> {
+    let (windows, workspaces) = get_window_and_workspace_list_for(socket, application, settings)?;
+
+    let Some((matched_window, matched_window_workspace)) =
+        get_matched_window_and_workspace(&windows, &workspaces, application, settings)?
+    else {
+        return Err(Report::new(AppError::NoMatch).wrap_err(format!(
+            "Application '{}' has no window to focus, `focus` does not launch it",
+            application.name
+        )));
+    };
+    if matched_window.is_focused {
+        return Ok(());
+    }
+    if is_hidden_workspace(matched_window_workspace, settings) {
+        bail!(AppError::AlreadyHidden);
+    }
+
+    if !matched_window_workspace.is_focused {
+        socket.send(Request::Action(Action::FocusWorkspace {
+            reference: WorkspaceReferenceArg::Id(matched_window_workspace.id),
+        }))?;
+    }
+    focus_window(socket, matched_window.id, settings)?;
+
+    Ok(())
+}
+
+/// Focuses the matched window if it is on the focused workspace. With `anywhere`, switches
+/// to the window's workspace first instead of failing.
+pub fn activate(
+    socket: &mut impl NiriIpc,
+    application: &Application,
+    settings: &Settings,
+    anywhere: bool,
+) -> Result<()> {
+    let (windows, workspaces) = get_window_and_workspace_list_for(socket, application, settings)?;
+
+    let (matched_window, matched_window_workspace) =
+        get_matched_window_and_workspace(&windows, &workspaces, application, settings)?
+            .ok_or(AppError::NoMatch)?;
 
     let focused_workspace = get_focused_workspace(&workspaces)?;
     if focused_workspace.id != matched_window_workspace.id {
-        bail!("The matched window is not in the focused workspace, cannot activate it.");
+        if !anywhere {
+            bail!(AppError::NotOnFocusedWorkspace("activate"));
+        }
+        if is_hidden_workspace(matched_window_workspace, settings) {
+            bail!(AppError::AlreadyHidden);
+        }
+
+        // Switch to the workspace of the matched window
+        socket.send(Request::Action(Action::FocusWorkspace {
+            reference: WorkspaceReferenceArg::Id(matched_window_workspace.id),
+        }))?;
     }
 
     // Focus the matched window
-    let _ = socket
-        .send(Request::Action(Action::FocusWindow {
-            id: matched_window.id,
-        }))
-        .into_diagnostic()?;
+    focus_window(socket, matched_window.id, settings)?;
+    if settings.verify() {
+        verify_focused(socket, matched_window.id)?;
+    }
 
     Ok(())
 }
 
-pub fn toggle(application: &Application) -> Result<()> {
-    let mut socket = Socket::connect().into_diagnostic()?;
-    let (windows, workspaces) = get_window_and_workspace_list(&mut socket)?;
+/// Closes the matched window, or with `all` or the `all` multi policy every matched window.
+/// Closing more than one window requires `confirm` to approve the list of windows first.
+pub fn close(
+    socket: &mut impl NiriIpc,
+    application: &Application,
+    settings: &Settings,
+    all: bool,
+    confirm: impl FnOnce(&[&Window]) -> Result<bool>,
+) -> Result<()> {
+    let (windows, _) = get_window_and_workspace_list_for(socket, application, settings)?;
 
-    let matched = get_matched_window_and_workspace(
-        &windows,
-        &workspaces,
-        &application.matches,
-        &application.excludes,
-    )?;
-    if matched.is_none() {
-        // No matched window, launch the application
-        return launch(&application);
+    let matched_windows = if all || settings.multi() == MultiPolicy::All {
+        match_application_windows(&windows, application)
+    } else {
+        select_application_window(&windows, application, settings)?
+            .and_then(|window_id| windows.iter().find(|window| window.id == window_id))
+            .into_iter()
+            .collect()
+    };
+    if matched_windows.is_empty() {
+        bail!(AppError::NoMatch);
     }
 
-    let (matched_window, matched_window_workspace) = matched.unwrap();
-
-    if let Some(focused_window) = get_focused_window(&windows) {
-        if focused_window.id == matched_window.id {
-            // Matched window is focused, hide it
-            let hidden_workspace = get_hidden_workspace(&workspaces)?;
-            let _ = socket
-                .send(Request::Action(Action::MoveWindowToWorkspace {
-                    window_id: Some(matched_window.id),
-                    reference: WorkspaceReferenceArg::Id(hidden_workspace.id),
-                    focus: false,
-                }))
-                .into_diagnostic()?;
-            return Ok(());
-        }
+    if matched_windows.len() > 1 && !confirm(&matched_windows)? {
+        bail!(AppError::Cancelled("Closing windows was cancelled."));
     }
 
-    let focused_workspace = get_focused_workspace(&workspaces)?;
-    if focused_workspace.id != matched_window_workspace.id {
-        // Move matched window to focused workspace and focus it
-        let _ = socket
-            .send(Request::Action(Action::MoveWindowToWorkspace {
-                window_id: Some(matched_window.id),
-                reference: WorkspaceReferenceArg::Id(focused_workspace.id),
-                focus: true,
-            }))
-            .into_diagnostic()?;
+    for window in matched_windows {
+        socket.send(Request::Action(Action::CloseWindow {
+            id: Some(window.id),
+        }))?;
     }
 
-    // Matched window is in focused workspace, focus it
-    let _ = socket
-        .send(Request::Action(Action::FocusWindow {
-            id: matched_window.id,
-        }))
-        .into_diagnostic()?;
-
     Ok(())
 }
+
+/// Sends SIGTERM, or with `force` SIGKILL, to the process that owns the matched window.
+pub fn kill(
+    socket: &mut impl NiriIpc,
+    application: &Application,
+    settings: &Settings,
+    force: bool,
+) -> Result<()> {
+    let (windows, _) = get_window_and_workspace_list_for(socket, application, settings)?;
+
+    let matched_window = select_application_window(&windows, application, settings)?
+        .and_then(|window_id| windows.iter().find(|window| window.id == window_id))
+        .ok_or(AppError::NoMatch)?;
+    let pid = matched_window.pid.ok_or_else(|| {
+        miette!(
+            "Niri does not know the process of window {}, cannot kill it.",
+            matched_window.id
+        )
+    })?;
+    // 0 and negative PIDs would signal whole process groups, if not every process we own
+    if pid <= 0 {
+        bail!(
+            "Niri reports the invalid PID {pid} for window {}, cannot kill it.",
+            matched_window.id
+        );
+    }
+
+    let signal = if force { libc::SIGKILL } else { libc::SIGTERM };
+    // SAFETY: `kill` has no memory safety preconditions.
+    if unsafe { libc::kill(pid, signal) } == -1 {
+        return Err(io::Error::last_os_error())
+            .into_diagnostic()
+            .context(format!("Failed to send signal to process {pid}"));
+    }
+
+    Ok(())
+}
+
+/// Launches, shows or focuses the application, or hides its window if it is already
+/// focused. With `fullscreen`, a focused window is toggled fullscreen instead of hidden.
+/// After hiding, focus moves as `after_hide` says, as with [`hide`].
+pub fn toggle(
+    socket: &mut impl NiriIpc,
+    application: &Application,
+    settings: &Settings,
+    fullscreen: bool,
+    after_hide: AfterHide,
+) -> Result<()> {
+    let (windows, workspaces) = get_window_and_workspace_list_for(socket, application, settings)?;
+
+    let matched = get_matched_window_and_workspace(&windows, &workspaces, application, settings)?;
+    if matched.is_none() {
+        // No matched window, launch the application
+        return launch(application, settings, None);
+    }
+
+    let (matched_window, matched_window_workspace) = matched.unwrap();
+
+    if let Some(focused_window) = get_focused_window(&windows)
+        && focused_window.id == matched_window.id
+    {
+        if fullscreen {
+            // Matched window is focused, toggle its fullscreen state
+            socket.send(Request::Action(Action::FullscreenWindow {
+                id: Some(matched_window.id),
+            }))?;
+            return Ok(());
+        }
+
+        return match application.on_focused {
+            OnFocused::Hide => {
+                // Matched window is focused, hide it
+                let hidden_workspace = get_hidden_workspace(&workspaces, settings)?;
+                if hidden_workspace.id == matched_window_workspace.id {
+                    bail!(AppError::AlreadyHidden);
+                }
+                hide_window(
+                    socket,
+                    &windows,
+                    &workspaces,
+                    matched_window,
+                    hidden_workspace,
+                    settings,
+                    after_hide,
+                )
+            }
+            // Matched window is focused, open another instance next to it
+            OnFocused::Launch => launch(application, settings, Some(matched_window)),
+            OnFocused::Nothing => Ok(()),
+        };
+    }
+
+    let focused_workspace = get_focused_workspace(&workspaces)?;
+    ensure_not_hidden(focused_workspace, settings, "show")?;
+    if focused_workspace.id != matched_window_workspace.id {
+        // Move matched window to focused workspace and focus it
+        socket.send(Request::Action(Action::MoveWindowToWorkspace {
+            window_id: Some(matched_window.id),
+            reference: WorkspaceReferenceArg::Id(focused_workspace.id),
+            focus: true,
+        }))?;
+        restore_floating(socket, matched_window, settings)?;
+    }
+
+    // Matched window is in focused workspace, focus it
+    focus_window(socket, matched_window.id, settings)?;
+
+    Ok(())
+}
+
+/// Switches to the workspace of the application's window, or back to the previously
+/// focused workspace if it is already focused. Unlike [`toggle`], the window is never moved,
+/// which suits applications pinned to a workspace of their own.
+pub fn toggle_workspace(
+    socket: &mut impl NiriIpc,
+    application: &Application,
+    settings: &Settings,
+) -> Result<()> {
+    let (windows, workspaces) = get_window_and_workspace_list_for(socket, application, settings)?;
+
+    let Some((matched_window, matched_window_workspace)) =
+        get_matched_window_and_workspace(&windows, &workspaces, application, settings)?
+    else {
+        // No matched window, launch the application
+        return launch(application, settings, None);
+    };
+
+    let focused_workspace = get_focused_workspace(&workspaces)?;
+    if focused_workspace.id == matched_window_workspace.id {
+        // Already on the workspace of the matched window, go back
+        socket.send(Request::Action(Action::FocusWorkspacePrevious {}))?;
+        return Ok(());
+    }
+    if is_hidden_workspace(matched_window_workspace, settings) {
+        bail!(AppError::AlreadyHidden);
+    }
+
+    // Switch to the workspace of the matched window and focus it
+    socket.send(Request::Action(Action::FocusWorkspace {
+        reference: WorkspaceReferenceArg::Id(matched_window_workspace.id),
+    }))?;
+    focus_window(socket, matched_window.id, settings)?;
+
+    Ok(())
+}
+
+/// Brings the application to the front with whatever it takes: launches it and, with a
+/// `timeout`, waits for its window to focus it; switches to the workspace of its window,
+/// unless `switch_workspace` is off; brings a hidden window back to the focused workspace;
+/// and leaves an already focused window alone. Without `launch_absent`, a missing window
+/// is an error.
+pub fn smart_raise(
+    socket: &mut impl NiriIpc,
+    application: &Application,
+    settings: &Settings,
+    launch_absent: bool,
+    timeout: Option<Duration>,
+    switch_workspace: bool,
+) -> Result<()> {
+    let (windows, workspaces) = get_window_and_workspace_list_for(socket, application, settings)?;
+
+    let Some((matched_window, matched_window_workspace)) =
+        get_matched_window_and_workspace(&windows, &workspaces, application, settings)?
+    else {
+        if !launch_absent {
+            bail!(AppError::NoMatch);
+        }
+        return match timeout {
+            Some(timeout) => launch_and_focus(socket, application, settings, timeout, None),
+            None => launch(application, settings, None),
+        };
+    };
+
+    if matched_window.is_focused {
+        // Matched window is already in front
+        return Ok(());
+    }
+
+    let focused_workspace = get_focused_workspace(&workspaces)?;
+    if focused_workspace.id != matched_window_workspace.id {
+        if is_hidden_workspace(matched_window_workspace, settings) {
+            // Bring the hidden window back rather than switching to the hidden workspace
+            ensure_not_hidden(focused_workspace, settings, "raise")?;
+            socket.send(Request::Action(Action::MoveWindowToWorkspace {
+                window_id: Some(matched_window.id),
+                reference: WorkspaceReferenceArg::Id(focused_workspace.id),
+                focus: true,
+            }))?;
+            restore_floating(socket, matched_window, settings)?;
+        } else {
+            if !switch_workspace {
+                bail!(AppError::NotOnFocusedWorkspace("raise"));
+            }
+            socket.send(Request::Action(Action::FocusWorkspace {
+                reference: WorkspaceReferenceArg::Id(matched_window_workspace.id),
+            }))?;
+        }
+    }
+
+    focus_window(socket, matched_window.id, settings)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{os::unix::process::ExitStatusExt, str::FromStr};
+
+    use niri_ipc::{Action, Request, Timestamp, WorkspaceReferenceArg};
+
+    use super::*;
+    use crate::{
+        config::{Application, MatchRule, Regex, Settings},
+        ipc::fake::{FakeIpc, window, workspace},
+    };
+
+    fn application(app_id: &str) -> Application {
+        Application {
+            name: app_id.to_string(),
+            matches: vec![MatchRule {
+                app_id: Some(Regex::from_str(app_id).unwrap()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn workspaces() -> Vec<Workspace> {
+        vec![
+            workspace(1, true, false),
+            workspace(2, false, false),
+            workspace(3, false, true),
+        ]
+    }
+
+    #[test]
+    fn expand_home_replaces_tilde() {
+        let home = Path::new("/home/user");
+
+        assert_eq!(
+            expand_home_with(PathBuf::from("~"), Some(home)).unwrap(),
+            PathBuf::from("/home/user")
+        );
+        assert_eq!(
+            expand_home_with(PathBuf::from("~/bin/app"), Some(home)).unwrap(),
+            PathBuf::from("/home/user/bin/app")
+        );
+    }
+
+    #[test]
+    fn expand_home_keeps_other_paths() {
+        let home = Path::new("/home/user");
+
+        for path in ["/usr/bin/app", "app", "bin/~/app", ""] {
+            assert_eq!(
+                expand_home_with(PathBuf::from(path), Some(home)).unwrap(),
+                PathBuf::from(path)
+            );
+        }
+        assert_eq!(
+            expand_home_with(PathBuf::from("/usr/bin/app"), None).unwrap(),
+            PathBuf::from("/usr/bin/app")
+        );
+    }
+
+    #[test]
+    fn expand_home_rejects_other_users() {
+        assert!(
+            expand_home_with(PathBuf::from("~other/app"), Some(Path::new("/home/user"))).is_err()
+        );
+    }
+
+    #[test]
+    fn expand_home_fails_without_home_directory() {
+        assert!(expand_home_with(PathBuf::from("~/app"), None).is_err());
+    }
+
+    #[test]
+    fn expand_env_vars_substitutes_set_variables() {
+        let lookup = |name: &str| (name == "MY_PROFILE").then(|| "work".to_string());
+
+        for (arg, expected) in [
+            ("--profile=$MY_PROFILE", "--profile=work"),
+            ("${MY_PROFILE}-1", "work-1"),
+            ("$MY_PROFILE/$MY_PROFILE", "work/work"),
+            ("costs $$5", "costs $5"),
+            ("$ alone", "$ alone"),
+            ("trailing $", "trailing $"),
+            ("$1", "$1"),
+            ("no variables", "no variables"),
+        ] {
+            assert_eq!(
+                expand_env_vars_with(arg, lookup).unwrap(),
+                expected,
+                "{arg}"
+            );
+        }
+    }
+
+    #[test]
+    fn expand_env_vars_fails_on_unset_variable() {
+        let err = expand_env_vars_with("--profile=${MISSING}", |_| None).unwrap_err();
+        assert!(err.to_string().contains("`MISSING`"));
+
+        assert!(expand_env_vars_with("${MISSING", |_| None).is_err());
+    }
+
+    #[test]
+    fn poll_interval_backs_off_to_maximum() {
+        let intervals = std::iter::successors(Some(MIN_POLL_INTERVAL), |&interval| {
+            Some(next_poll_interval(interval))
+        })
+        .take(5)
+        .map(|interval| interval.as_millis())
+        .collect::<Vec<_>>();
+
+        assert_eq!(intervals, [25, 50, 100, 200, 200]);
+        for interval in [MIN_POLL_INTERVAL, MAX_POLL_INTERVAL] {
+            let jittered = with_jitter(interval);
+            assert!(jittered >= interval && jittered <= interval + interval / 4);
+        }
+    }
+
+    #[test]
+    fn has_matched_windows_counts_any_number_of_matches() {
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "kitty", 1, false), window(2, "kitty", 1, true)],
+            workspaces(),
+        );
+
+        assert!(has_matched_windows(&mut ipc, &application("kitty")).unwrap());
+        assert!(!has_matched_windows(&mut ipc, &application("firefox")).unwrap());
+    }
+
+    #[test]
+    fn kill_terminates_process_of_matched_window() {
+        let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+        let mut matched_window = window(1, "firefox", 1, false);
+        matched_window.pid = Some(child.id() as i32);
+        let mut ipc = FakeIpc::new(vec![matched_window], workspaces());
+
+        kill(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            false,
+        )
+        .unwrap();
+
+        let status = child.wait().unwrap();
+        assert_eq!(status.signal(), Some(libc::SIGTERM));
+    }
+
+    #[test]
+    fn kill_fails_without_pid() {
+        let mut matched_window = window(1, "firefox", 1, false);
+        matched_window.pid = None;
+        let mut ipc = FakeIpc::new(vec![matched_window], workspaces());
+
+        let err = kill(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            true,
+        )
+        .unwrap_err();
+
+        assert!(err.to_string().contains("cannot kill it"));
+    }
+
+    #[test]
+    fn kill_refuses_process_group_pids() {
+        for pid in [0, -1] {
+            let mut matched_window = window(1, "firefox", 1, false);
+            matched_window.pid = Some(pid);
+            let mut ipc = FakeIpc::new(vec![matched_window], workspaces());
+
+            let err = kill(
+                &mut ipc,
+                &application("firefox"),
+                &Settings::default(),
+                true,
+            )
+            .unwrap_err();
+
+            assert_eq!(
+                err.to_string(),
+                format!("Niri reports the invalid PID {pid} for window 1, cannot kill it.")
+            );
+        }
+    }
+
+    #[test]
+    fn show_moves_window_to_focused_workspace() {
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 2, false), window(2, "kitty", 1, true)],
+            workspaces(),
+        );
+
+        show(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            true,
+            None,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [
+                Request::Action(Action::MoveWindowToWorkspace {
+                    window_id: Some(1),
+                    reference: WorkspaceReferenceArg::Id(1),
+                    focus: true,
+                }),
+                Request::Action(Action::FocusWindow { id: 1 }),
+            ]
+        ));
+    }
+
+    #[test]
+    fn show_focuses_window_on_focused_workspace() {
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 1, false), window(2, "kitty", 1, true)],
+            workspaces(),
+        );
+
+        show(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            true,
+            None,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [Request::Action(Action::FocusWindow { id: 1 })]
+        ));
+    }
+
+    #[test]
+    fn show_fails_without_matched_window() {
+        let mut ipc = FakeIpc::new(vec![window(2, "kitty", 1, true)], workspaces());
+
+        let err = show(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            true,
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(AppError::NoMatch)));
+        assert!(ipc.actions().is_empty());
+    }
+
+    #[test]
+    fn hide_moves_focused_window_to_hidden_workspace() {
+        let mut ipc = FakeIpc::new(vec![window(1, "firefox", 1, true)], workspaces());
+
+        hide(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            AfterHide::Default,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [Request::Action(Action::MoveWindowToWorkspace {
+                window_id: Some(1),
+                reference: WorkspaceReferenceArg::Id(3),
+                focus: false,
+            })]
+        ));
+    }
+
+    #[test]
+    fn hide_prefers_named_hidden_workspace() {
+        let mut workspaces = workspaces();
+        workspaces[1].name = Some("stash".to_string());
+        let mut ipc = FakeIpc::new(vec![window(1, "firefox", 1, true)], workspaces);
+        let settings = Settings {
+            hidden_workspace: Some(Regex::from_str("^stash$").unwrap()),
+            ..Default::default()
+        };
+
+        hide(
+            &mut ipc,
+            &application("firefox"),
+            &settings,
+            AfterHide::Default,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [Request::Action(Action::MoveWindowToWorkspace {
+                window_id: Some(1),
+                reference: WorkspaceReferenceArg::Id(2),
+                focus: false,
+            })]
+        ));
+    }
+
+    #[test]
+    fn hide_uses_hidden_workspace_on_the_focused_output() {
+        let mut workspaces = vec![
+            workspace(1, false, false),
+            workspace(2, false, true),
+            workspace(3, true, false),
+            workspace(4, false, true),
+        ];
+        for workspace in &mut workspaces[2..] {
+            workspace.output = Some("HDMI-A-1".to_string());
+        }
+        let mut ipc = FakeIpc::new(vec![window(1, "firefox", 3, true)], workspaces);
+
+        hide(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            AfterHide::Default,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [Request::Action(Action::MoveWindowToWorkspace {
+                window_id: Some(1),
+                reference: WorkspaceReferenceArg::Id(4),
+                focus: false,
+            })]
+        ));
+    }
+
+    #[test]
+    fn hide_fails_when_window_is_not_focused() {
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 1, false), window(2, "kitty", 1, true)],
+            workspaces(),
+        );
+
+        let err = hide(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            AfterHide::Default,
+        )
+        .unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(AppError::NotFocused(_))));
+        assert!(ipc.actions().is_empty());
+    }
+
+    #[test]
+    fn hide_focuses_the_given_workspace() {
+        let mut named = workspace(4, false, false);
+        named.name = Some("chat".to_string());
+        let mut workspaces = workspaces();
+        workspaces.push(named);
+        let mut ipc = FakeIpc::new(vec![window(1, "firefox", 1, true)], workspaces);
+
+        hide(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            AfterHide::FocusWorkspace("chat"),
+        )
+        .unwrap();
+        let err = hide(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            AfterHide::FocusWorkspace("mail"),
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [
+                Request::Action(Action::MoveWindowToWorkspace {
+                    window_id: Some(1),
+                    reference: WorkspaceReferenceArg::Id(3),
+                    focus: false,
+                }),
+                Request::Action(Action::FocusWorkspace {
+                    reference: WorkspaceReferenceArg::Id(4),
+                }),
+            ]
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Unknown workspace 'mail', available workspaces: 1, 2, 3, chat"
+        );
+    }
+
+    #[test]
+    fn hide_returns_to_previous_workspace_when_leaving_it_empty() {
+        let hide_firefox = |windows| {
+            let mut ipc = FakeIpc::new(windows, workspaces());
+            hide(
+                &mut ipc,
+                &application("firefox"),
+                &Settings::default(),
+                AfterHide::PreviousWorkspace,
+            )
+            .unwrap();
+            ipc.actions().into_iter().cloned().collect::<Vec<_>>()
+        };
+
+        let alone = hide_firefox(vec![
+            window(1, "firefox", 1, true),
+            window(2, "kitty", 2, false),
+        ]);
+        let shared = hide_firefox(vec![
+            window(1, "firefox", 1, true),
+            window(2, "kitty", 1, false),
+        ]);
+
+        assert!(matches!(
+            alone.as_slice(),
+            [
+                Request::Action(Action::MoveWindowToWorkspace {
+                    window_id: Some(1),
+                    reference: WorkspaceReferenceArg::Id(3),
+                    focus: false,
+                }),
+                Request::Action(Action::FocusWorkspacePrevious {}),
+            ]
+        ));
+        assert!(matches!(
+            shared.as_slice(),
+            [Request::Action(Action::MoveWindowToWorkspace { .. })]
+        ));
+    }
+
+    #[test]
+    fn hide_focuses_previous_window() {
+        let mut windows = vec![
+            window(1, "firefox", 1, true),
+            window(2, "kitty", 1, false),
+            window(3, "kitty", 1, false),
+        ];
+        windows[1].focus_timestamp = Some(Timestamp { secs: 20, nanos: 0 });
+        windows[2].focus_timestamp = Some(Timestamp { secs: 10, nanos: 0 });
+        let mut ipc = FakeIpc::new(windows, workspaces());
+
+        hide(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            AfterHide::FocusPreviousWindow,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [
+                Request::Action(Action::MoveWindowToWorkspace {
+                    window_id: Some(1),
+                    reference: WorkspaceReferenceArg::Id(3),
+                    focus: false,
+                }),
+                Request::Action(Action::FocusWindow { id: 2 }),
+            ]
+        ));
+    }
+
+    #[test]
+    fn toggle_hides_focused_window() {
+        let mut ipc = FakeIpc::new(vec![window(1, "firefox", 1, true)], workspaces());
+
+        toggle(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            false,
+            AfterHide::Default,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [Request::Action(Action::MoveWindowToWorkspace {
+                window_id: Some(1),
+                reference: WorkspaceReferenceArg::Id(3),
+                focus: false,
+            })]
+        ));
+    }
+
+    #[test]
+    fn show_moves_window_to_output() {
+        let mut workspaces = workspaces();
+        let mut other_output = workspace(4, false, false);
+        other_output.output = Some("HDMI-A-1".to_string());
+        other_output.is_active = true;
+        workspaces.push(other_output);
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 2, false), window(2, "kitty", 1, true)],
+            workspaces,
+        );
+
+        show(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            true,
+            Some("HDMI-A-1"),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [
+                Request::Action(Action::MoveWindowToWorkspace {
+                    window_id: Some(1),
+                    reference: WorkspaceReferenceArg::Id(4),
+                    focus: true,
+                }),
+                Request::Action(Action::FocusWindow { id: 1 }),
+            ]
+        ));
+    }
+
+    #[test]
+    fn show_fails_for_unknown_output() {
+        let mut ipc = FakeIpc::new(vec![window(1, "firefox", 2, false)], workspaces());
+
+        let err = show(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            true,
+            Some("HDMI-A-1"),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("available outputs: DP-1"));
+        assert!(ipc.actions().is_empty());
+    }
+
+    #[test]
+    fn show_without_focus_keeps_current_focus() {
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 2, false), window(2, "kitty", 1, true)],
+            workspaces(),
+        );
+
+        show(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            false,
+            None,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [Request::Action(Action::MoveWindowToWorkspace {
+                window_id: Some(1),
+                reference: WorkspaceReferenceArg::Id(1),
+                focus: false,
+            })]
+        ));
+    }
+
+    #[test]
+    fn show_restores_floating_window() {
+        let mut windows = vec![window(1, "firefox", 3, false), window(2, "kitty", 1, true)];
+        windows[0].is_floating = true;
+        let mut ipc = FakeIpc::new(windows, workspaces());
+        let settings = Settings {
+            preserve_floating: Some(true),
+            ..Default::default()
+        };
+
+        show(&mut ipc, &application("firefox"), &settings, true, None).unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [
+                Request::Action(Action::MoveWindowToWorkspace {
+                    window_id: Some(1),
+                    reference: WorkspaceReferenceArg::Id(1),
+                    focus: true,
+                }),
+                Request::Action(Action::MoveWindowToFloating { id: Some(1) }),
+                Request::Action(Action::FocusWindow { id: 1 }),
+            ]
+        ));
+    }
+
+    #[test]
+    fn toggle_launches_another_instance_when_focused() {
+        let application = Application {
+            spawn: Some(vec!["true".to_string()]),
+            on_focused: OnFocused::Launch,
+            ..application("firefox")
+        };
+        let mut ipc = FakeIpc::new(vec![window(1, "firefox", 1, true)], workspaces());
+
+        toggle(
+            &mut ipc,
+            &application,
+            &Settings::default(),
+            false,
+            AfterHide::Default,
+        )
+        .unwrap();
+
+        assert!(ipc.actions().is_empty());
+    }
+
+    #[test]
+    fn toggle_does_nothing_when_focused() {
+        let application = Application {
+            on_focused: OnFocused::Nothing,
+            ..application("firefox")
+        };
+        let mut ipc = FakeIpc::new(vec![window(1, "firefox", 1, true)], workspaces());
+
+        toggle(
+            &mut ipc,
+            &application,
+            &Settings::default(),
+            false,
+            AfterHide::Default,
+        )
+        .unwrap();
+
+        assert!(ipc.actions().is_empty());
+    }
+
+    #[test]
+    fn toggle_workspace_switches_to_the_window_workspace_and_back() {
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "obsidian", 2, false), window(2, "kitty", 1, true)],
+            workspaces(),
+        );
+        toggle_workspace(&mut ipc, &application("obsidian"), &Settings::default()).unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [
+                Request::Action(Action::FocusWorkspace {
+                    reference: WorkspaceReferenceArg::Id(2),
+                }),
+                Request::Action(Action::FocusWindow { id: 1 }),
+            ]
+        ));
+
+        let mut ipc = FakeIpc::new(vec![window(1, "obsidian", 1, true)], workspaces());
+        toggle_workspace(&mut ipc, &application("obsidian"), &Settings::default()).unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [Request::Action(Action::FocusWorkspacePrevious {})]
+        ));
+    }
+
+    #[test]
+    fn toggle_workspace_does_not_switch_to_a_hidden_workspace() {
+        let mut ipc = FakeIpc::new(vec![window(1, "obsidian", 3, false)], workspaces());
+
+        let err =
+            toggle_workspace(&mut ipc, &application("obsidian"), &Settings::default()).unwrap_err();
+
+        assert!(matches!(err.downcast_ref(), Some(AppError::AlreadyHidden)));
+        assert!(ipc.actions().is_empty());
+    }
+
+    #[test]
+    fn toggle_workspace_does_not_switch_to_a_named_hidden_workspace() {
+        let mut workspaces = workspaces();
+        workspaces[1].name = Some("stash".to_string());
+        let mut ipc = FakeIpc::new(vec![window(1, "obsidian", 2, false)], workspaces);
+        let settings = Settings {
+            hidden_workspace: Some(Regex::from_str("^stash$").unwrap()),
+            ..Default::default()
+        };
+
+        let err = toggle_workspace(&mut ipc, &application("obsidian"), &settings).unwrap_err();
+
+        assert!(matches!(err.downcast_ref(), Some(AppError::AlreadyHidden)));
+        assert!(ipc.actions().is_empty());
+    }
+
+    #[test]
+    fn smart_raise_switches_to_the_window_workspace() {
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 2, false), window(2, "kitty", 1, true)],
+            workspaces(),
+        );
+
+        smart_raise(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            true,
+            None,
+            true,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [
+                Request::Action(Action::FocusWorkspace {
+                    reference: WorkspaceReferenceArg::Id(2),
+                }),
+                Request::Action(Action::FocusWindow { id: 1 }),
+            ]
+        ));
+    }
+
+    #[test]
+    fn smart_raise_brings_back_a_hidden_window() {
+        let mut ipc = FakeIpc::new(vec![window(1, "firefox", 3, false)], workspaces());
+
+        smart_raise(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            true,
+            None,
+            false,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [
+                Request::Action(Action::MoveWindowToWorkspace {
+                    window_id: Some(1),
+                    reference: WorkspaceReferenceArg::Id(1),
+                    focus: true,
+                }),
+                Request::Action(Action::FocusWindow { id: 1 }),
+            ]
+        ));
+    }
+
+    #[test]
+    fn smart_raise_leaves_a_focused_window_alone() {
+        let mut ipc = FakeIpc::new(vec![window(1, "firefox", 1, true)], workspaces());
+
+        smart_raise(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            true,
+            None,
+            true,
+        )
+        .unwrap();
+
+        assert!(ipc.actions().is_empty());
+    }
+
+    #[test]
+    fn smart_raise_honors_disabled_steps() {
+        let mut ipc = FakeIpc::new(vec![window(1, "firefox", 2, false)], workspaces());
+        let raise = |ipc: &mut FakeIpc, application: &Application| {
+            smart_raise(ipc, application, &Settings::default(), false, None, false)
+        };
+
+        let not_switched = raise(&mut ipc, &application("firefox")).unwrap_err();
+        let not_launched = raise(&mut ipc, &application("kitty")).unwrap_err();
+
+        assert!(matches!(
+            not_switched.downcast_ref(),
+            Some(AppError::NotOnFocusedWorkspace("raise"))
+        ));
+        assert!(matches!(
+            not_launched.downcast_ref(),
+            Some(AppError::NoMatch)
+        ));
+        assert!(ipc.actions().is_empty());
+    }
+
+    #[test]
+    fn toggle_fullscreen_toggles_focused_window() {
+        let mut ipc = FakeIpc::new(vec![window(1, "firefox", 1, true)], workspaces());
+
+        toggle(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            true,
+            AfterHide::Default,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [Request::Action(Action::FullscreenWindow { id: Some(1) })]
+        ));
+    }
+
+    #[test]
+    fn toggle_fullscreen_raises_unfocused_window() {
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 3, false), window(2, "kitty", 1, true)],
+            workspaces(),
+        );
+
+        toggle(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            true,
+            AfterHide::Default,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [
+                Request::Action(Action::MoveWindowToWorkspace {
+                    window_id: Some(1),
+                    reference: WorkspaceReferenceArg::Id(1),
+                    focus: true,
+                }),
+                Request::Action(Action::FocusWindow { id: 1 }),
+            ]
+        ));
+    }
+
+    #[test]
+    fn toggle_shows_hidden_window() {
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 3, false), window(2, "kitty", 1, true)],
+            workspaces(),
+        );
+
+        toggle(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            false,
+            AfterHide::Default,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [
+                Request::Action(Action::MoveWindowToWorkspace {
+                    window_id: Some(1),
+                    reference: WorkspaceReferenceArg::Id(1),
+                    focus: true,
+                }),
+                Request::Action(Action::FocusWindow { id: 1 }),
+            ]
+        ));
+    }
+
+    #[test]
+    fn show_fails_when_focused_workspace_is_hidden() {
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 2, false)],
+            vec![
+                workspace(1, false, false),
+                workspace(2, false, false),
+                workspace(3, true, true),
+            ],
+        );
+
+        let err = show(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            true,
+            None,
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(AppError::HiddenWorkspaceFocused("show"))
+        ));
+        assert!(ipc.actions().is_empty());
+    }
+
+    #[test]
+    fn toggle_fails_when_focused_workspace_is_named_hidden_workspace() {
+        let mut stash = workspace(2, true, false);
+        stash.name = Some("stash".to_string());
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 1, false), window(2, "kitty", 2, true)],
+            vec![workspace(1, false, false), stash],
+        );
+        let settings = Settings {
+            hidden_workspace: Some(Regex::from_str("^stash$").unwrap()),
+            ..Default::default()
+        };
+
+        let err = toggle(
+            &mut ipc,
+            &application("firefox"),
+            &settings,
+            false,
+            AfterHide::Default,
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(AppError::HiddenWorkspaceFocused("show"))
+        ));
+        assert!(ipc.actions().is_empty());
+    }
+
+    #[test]
+    fn close_all_closes_every_matched_window_after_confirmation() {
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "kitty", 1, true), window(2, "kitty", 2, false)],
+            workspaces(),
+        );
+
+        close(
+            &mut ipc,
+            &application("kitty"),
+            &Settings::default(),
+            true,
+            |windows| {
+                assert_eq!(windows.len(), 2);
+                Ok(true)
+            },
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [
+                Request::Action(Action::CloseWindow { id: Some(1) }),
+                Request::Action(Action::CloseWindow { id: Some(2) }),
+            ]
+        ));
+    }
+
+    #[test]
+    fn close_follows_the_multi_policy() {
+        let windows = vec![window(1, "kitty", 1, true), window(2, "kitty", 2, false)];
+        let settings = |multi| Settings {
+            multi: Some(multi),
+            first_match: Some(true),
+            ..Default::default()
+        };
+        let mut ipc = FakeIpc::new(windows.clone(), workspaces());
+        close(
+            &mut ipc,
+            &application("kitty"),
+            &settings(MultiPolicy::All),
+            false,
+            |_| Ok(true),
+        )
+        .unwrap();
+        assert_eq!(ipc.actions().len(), 2);
+
+        let mut ipc = FakeIpc::new(windows, workspaces());
+        let err = close(
+            &mut ipc,
+            &application("kitty"),
+            &settings(MultiPolicy::Error),
+            false,
+            |_| Ok(true),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(AppError::MultipleMatches(_))
+        ));
+        assert!(ipc.actions().is_empty());
+    }
+
+    #[test]
+    fn close_all_does_nothing_when_not_confirmed() {
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "kitty", 1, true), window(2, "kitty", 2, false)],
+            workspaces(),
+        );
+
+        assert!(
+            close(
+                &mut ipc,
+                &application("kitty"),
+                &Settings::default(),
+                true,
+                |_| Ok(false)
+            )
+            .is_err()
+        );
+        assert!(ipc.actions().is_empty());
+    }
+
+    #[test]
+    fn close_single_window_without_confirmation() {
+        let mut ipc = FakeIpc::new(vec![window(1, "kitty", 1, true)], workspaces());
+
+        close(
+            &mut ipc,
+            &application("kitty"),
+            &Settings::default(),
+            true,
+            |_| panic!("confirmation requested for a single window"),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [Request::Action(Action::CloseWindow { id: Some(1) })]
+        ));
+    }
+
+    #[test]
+    fn show_rematches_until_timeout() {
+        let mut ipc = FakeIpc::new(vec![window(2, "kitty", 1, true)], workspaces());
+        let settings = Settings {
+            rematch_timeout_ms: Some(250),
+            ..Default::default()
+        };
+
+        assert!(show(&mut ipc, &application("firefox"), &settings, true, None).is_err());
+        let window_requests = ipc
+            .requests
+            .iter()
+            .filter(|request| matches!(request, Request::Windows))
+            .count();
+        assert!(window_requests > 1);
+    }
+
+    #[test]
+    fn verify_focus_refocuses_when_focus_did_not_take() {
+        let settings = Settings {
+            verify_focus: Some(true),
+            ..Default::default()
+        };
+        // The fake does not change focus, so window 1 stays unfocused and window 2 focused
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 1, false), window(2, "kitty", 1, true)],
+            workspaces(),
+        );
+
+        activate(&mut ipc, &application("firefox"), &settings, false).unwrap();
+        activate(&mut ipc, &application("kitty"), &settings, false).unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [
+                Request::Action(Action::FocusWindow { id: 1 }),
+                Request::Action(Action::FocusWindow { id: 1 }),
+                Request::Action(Action::FocusWindow { id: 2 }),
+            ]
+        ));
+    }
+
+    #[test]
+    fn verify_fails_when_show_did_not_take_effect() {
+        let settings = Settings {
+            verify: Some(true),
+            ..Default::default()
+        };
+        // The fake neither moves nor focuses windows, so only the already focused window
+        // on the focused workspace passes verification
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 2, false), window(2, "kitty", 1, true)],
+            workspaces(),
+        );
+
+        show(&mut ipc, &application("kitty"), &settings, true, None).unwrap();
+        let err = show(&mut ipc, &application("firefox"), &settings, true, None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Verification failed: window 1 is not on workspace 1"
+        );
+
+        let err = activate(&mut ipc, &application("firefox"), &settings, true).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Verification failed: window 1 is not focused"
+        );
+    }
+
+    #[test]
+    fn activate_fails_on_other_workspace() {
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 2, false), window(2, "kitty", 1, true)],
+            workspaces(),
+        );
+
+        let err = activate(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            false,
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(AppError::NotOnFocusedWorkspace(_))
+        ));
+        assert!(ipc.actions().is_empty());
+    }
+
+    #[test]
+    fn activate_anywhere_switches_workspace() {
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 2, false), window(2, "kitty", 1, true)],
+            workspaces(),
+        );
+
+        activate(
+            &mut ipc,
+            &application("firefox"),
+            &Settings::default(),
+            true,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [
+                Request::Action(Action::FocusWorkspace {
+                    reference: WorkspaceReferenceArg::Id(2),
+                }),
+                Request::Action(Action::FocusWindow { id: 1 }),
+            ]
+        ));
+    }
+
+    #[test]
+    fn activate_anywhere_fails_on_named_hidden_workspace() {
+        let mut workspaces = workspaces();
+        workspaces[1].name = Some("stash".to_string());
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 2, false), window(2, "kitty", 1, true)],
+            workspaces,
+        );
+        let settings = Settings {
+            hidden_workspace: Some(Regex::from_str("^stash$").unwrap()),
+            ..Default::default()
+        };
+
+        let err = activate(&mut ipc, &application("firefox"), &settings, true).unwrap_err();
+
+        assert!(matches!(err.downcast_ref(), Some(AppError::AlreadyHidden)));
+        assert!(ipc.actions().is_empty());
+    }
+
+    #[test]
+    fn focus_switches_workspace_and_never_launches() {
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 2, false), window(2, "kitty", 1, true)],
+            workspaces(),
+        );
+
+        focus(&mut ipc, &application("firefox"), &Settings::default()).unwrap();
+        focus(&mut ipc, &application("kitty"), &Settings::default()).unwrap();
+        let err = focus(&mut ipc, &application("obsidian"), &Settings::default()).unwrap_err();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [
+                Request::Action(Action::FocusWorkspace {
+                    reference: WorkspaceReferenceArg::Id(2),
+                }),
+                Request::Action(Action::FocusWindow { id: 1 }),
+            ]
+        ));
+        assert!(matches!(err.downcast_ref(), Some(AppError::NoMatch)));
+        assert_eq!(
+            err.to_string(),
+            "Application 'obsidian' has no window to focus, `focus` does not launch it"
+        );
+    }
+
+    #[test]
+    fn launch_returns_without_waiting_for_process() {
+        let application = Application {
+            name: "sleep".to_string(),
+            spawn: Some(vec!["sleep".to_string(), "5".to_string()]),
+            ..Default::default()
+        };
+
+        let start = Instant::now();
+        launch(&application, &Settings::default(), None).unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn launch_reports_missing_command() {
+        let application = Application {
+            name: "missing".to_string(),
+            spawn: Some(vec!["niri-app-hotkey-missing-command".to_string()]),
+            ..Default::default()
+        };
+
+        let err = launch(&application, &Settings::default(), None).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Command \"niri-app-hotkey-missing-command\" of application 'missing' not found"
+        );
+    }
+
+    #[test]
+    fn launch_reports_process_that_exits_with_failure_right_away() {
+        let application = Application {
+            name: "failing".to_string(),
+            spawn_sh: Some("exit 3".to_string()),
+            ..Default::default()
+        };
+
+        let err = launch(&application, &Settings::default(), None).unwrap_err();
+
+        assert!(err.to_string().contains("exited right after launching"));
+    }
+
+    #[test]
+    fn wait_with_timeout_returns_none_for_running_process() {
+        let mut child = Command::new("sleep").arg("5").spawn().unwrap();
+
+        let start = Instant::now();
+        let status = wait_with_timeout(&mut child, Duration::from_millis(50)).unwrap();
+
+        assert!(status.is_none());
+        assert!(start.elapsed() < Duration::from_secs(1));
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+
+    #[test]
+    fn command_line_passes_multiline_spawn_sh_as_one_argument() {
+        let script = "mkdir -p /tmp/scratch\ncd /tmp/scratch && exec kitty\n";
+        let application = Application {
+            spawn_sh: Some(script.to_string()),
+            ..Default::default()
+        };
+
+        let (command, args) = command_line(&application, &Settings::default(), None).unwrap();
+
+        assert_eq!(command, PathBuf::from("sh"));
+        assert_eq!(args, ["-c", script]);
+    }
+
+    #[test]
+    fn format_command_line_quotes_arguments_for_the_shell() {
+        let application = Application {
+            spawn: Some(
+                ["kitty", "--title", "it's {app_id}", "--class=term"]
+                    .map(String::from)
+                    .to_vec(),
+            ),
+            ..Default::default()
+        };
+        let window = window(7, "kitty", 1, true);
+
+        assert_eq!(
+            format_command_line(&application, &Settings::default(), Some(&window)).unwrap(),
+            r"kitty --title 'it'\''s kitty' --class=term"
+        );
+    }
+
+    #[test]
+    fn expand_placeholders_uses_window_properties() {
+        let window = window(7, "firefox", 1, true);
+
+        assert_eq!(
+            expand_placeholders("--id={window_id} {app_id}", Some(&window), false),
+            "--id=7 firefox"
+        );
+    }
+
+    #[test]
+    fn spawn_sh_quotes_placeholders_for_the_shell() {
+        let app_id = "x;echo injected 'quoted'";
+        let application = Application {
+            spawn_sh: Some("printf %s {app_id}".to_string()),
+            ..Default::default()
+        };
+        let window = window(7, app_id, 1, true);
+
+        let (command, args) =
+            command_line(&application, &Settings::default(), Some(&window)).unwrap();
+        let output = Command::new(command).args(args).output().unwrap();
+
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), app_id);
+    }
+
+    #[test]
+    fn expand_placeholders_without_window_are_empty() {
+        assert_eq!(
+            expand_placeholders("--id={window_id}", None, false),
+            "--id="
+        );
+        assert_eq!(expand_placeholders("{app_id}", None, false), "");
+        assert_eq!(expand_placeholders("{app_id}", None, true), "''");
+    }
+
+    #[test]
+    fn expand_placeholders_keeps_other_braces() {
+        assert_eq!(
+            expand_placeholders("echo ${HOME} {a,b} {title}", None, true),
+            "echo ${HOME} {a,b} {title}"
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    thread,
+    time::Duration,
+};
+
+use miette::{Context, IntoDiagnostic, Result};
+use niri_ipc::{Event, Request, Response, Window};
+
+use crate::ipc::{self, NiriIpc};
+
+/// How long to wait before subscribing again after the event stream ended.
+const RESUBSCRIBE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Live copy of Niri's window list, kept up to date from the event stream. Empty while
+/// there is no stream, in which case windows are fetched on demand.
+#[derive(Clone, Default)]
+pub struct WindowCache {
+    windows: Arc<RwLock<Option<Vec<Window>>>>,
+}
+
+impl WindowCache {
+    /// Starts following Niri's event stream in the background. When the stream ends, e.g.
+    /// because Niri restarted, the cache is cleared and the subscription retried.
+    pub fn subscribe(socket_path: Option<PathBuf>) -> Self {
+        let cache = Self::default();
+        let writer = cache.clone();
+        thread::spawn(move || {
+            loop {
+                if let Err(err) = writer.follow(socket_path.as_deref())
+                    && writer.get().is_some()
+                {
+                    eprintln!("Warning: window cache disabled until Niri is reachable: {err}");
+                }
+                writer.set(None);
+                thread::sleep(RESUBSCRIBE_INTERVAL);
+            }
+        });
+
+        cache
+    }
+
+    fn follow(&self, socket_path: Option<&Path>) -> Result<()> {
+        let mut socket = ipc::connect(socket_path)?;
+        NiriIpc::send(&mut socket, Request::EventStream)?;
+
+        let mut read_event = socket.read_events();
+        loop {
+            let event = read_event()
+                .into_diagnostic()
+                .context("Failed to read event from Niri")?;
+            let mut windows = self.windows.write().unwrap_or_else(|err| err.into_inner());
+            apply_event(&mut windows, event);
+        }
+    }
+
+    fn get(&self) -> Option<Vec<Window>> {
+        self.windows
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    fn set(&self, windows: Option<Vec<Window>>) {
+        *self.windows.write().unwrap_or_else(|err| err.into_inner()) = windows;
+    }
+}
+
+/// Updates the window list with an event. Until Niri sends the full list, which it does
+/// right after subscribing, the list is unknown and other window events are ignored.
+fn apply_event(windows: &mut Option<Vec<Window>>, event: Event) {
+    if let Event::WindowsChanged { windows: all } = event {
+        *windows = Some(all);
+        return;
+    }
+    let Some(windows) = windows else {
+        return;
+    };
+
+    match event {
+        Event::WindowOpenedOrChanged { window } => {
+            // A newly focused window takes the focus from all others
+            if window.is_focused {
+                for other in windows.iter_mut() {
+                    other.is_focused = false;
+                }
+            }
+            match windows.iter_mut().find(|other| other.id == window.id) {
+                Some(existing) => *existing = window,
+                None => windows.push(window),
+            }
+        }
+        Event::WindowClosed { id } => windows.retain(|window| window.id != id),
+        Event::WindowFocusChanged { id } => {
+            for window in windows.iter_mut() {
+                window.is_focused = Some(window.id) == id;
+            }
+        }
+        Event::WindowUrgencyChanged { id, urgent } => {
+            if let Some(window) = windows.iter_mut().find(|window| window.id == id) {
+                window.is_urgent = urgent;
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Answers window queries from a [`WindowCache`] and sends everything else to Niri. Once a
+/// command has sent an action, later queries go to Niri as well, since the cache may not
+/// have caught up with the action's effect yet.
+pub struct CachedSocket<'a, S> {
+    cache: &'a WindowCache,
+    socket: &'a mut S,
+    acted: bool,
+}
+
+impl<'a, S: NiriIpc> CachedSocket<'a, S> {
+    pub fn new(cache: &'a WindowCache, socket: &'a mut S) -> Self {
+        Self {
+            cache,
+            socket,
+            acted: false,
+        }
+    }
+}
+
+impl<S: NiriIpc> NiriIpc for CachedSocket<'_, S> {
+    fn send(&mut self, request: Request) -> Result<Response> {
+        match request {
+            Request::Windows if !self.acted => {
+                if let Some(windows) = self.cache.get() {
+                    return Ok(Response::Windows(windows));
+                }
+            }
+            Request::Windows | Request::WorkspacesWithHidden => {}
+            _ => self.acted = true,
+        }
+
+        self.socket.send(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use niri_ipc::Action;
+
+    use super::*;
+    use crate::ipc::fake::{FakeIpc, window};
+
+    #[test]
+    fn apply_event_tracks_windows_and_focus() {
+        let mut windows = None;
+        apply_event(&mut windows, Event::WindowClosed { id: 1 });
+        assert!(windows.is_none());
+
+        let events = [
+            Event::WindowsChanged {
+                windows: vec![window(1, "firefox", 1, true), window(2, "kitty", 1, false)],
+            },
+            Event::WindowOpenedOrChanged {
+                window: window(3, "mpv", 1, true),
+            },
+            Event::WindowClosed { id: 1 },
+            Event::WindowFocusChanged { id: Some(2) },
+        ];
+        for event in events {
+            apply_event(&mut windows, event);
+        }
+
+        let windows = windows.unwrap();
+        let state = windows
+            .iter()
+            .map(|window| (window.id, window.is_focused))
+            .collect::<Vec<_>>();
+        assert_eq!(state, [(2, true), (3, false)]);
+    }
+
+    #[test]
+    fn cached_socket_answers_queries_until_an_action_is_sent() {
+        let cache = WindowCache::default();
+        cache.set(Some(vec![window(1, "firefox", 1, true)]));
+        let mut ipc = FakeIpc::new(vec![window(2, "kitty", 1, true)], vec![]);
+        let mut socket = CachedSocket::new(&cache, &mut ipc);
+
+        let window_ids = |response: Response| match response {
+            Response::Windows(windows) => windows.iter().map(|window| window.id).collect(),
+            _ => vec![],
+        };
+        assert_eq!(window_ids(socket.send(Request::Windows).unwrap()), [1]);
+        socket
+            .send(Request::Action(Action::FocusWindow { id: 1 }))
+            .unwrap();
+        assert_eq!(window_ids(socket.send(Request::Windows).unwrap()), [2]);
+    }
+}
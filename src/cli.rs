@@ -1,48 +1,424 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
-use clap::{Parser, Subcommand};
+use clap::{ArgGroup, Args, Parser, Subcommand};
 use directories::ProjectDirs;
 use miette::{Result, miette};
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    config::{Application, ConfigFormat, MatchRule, MultiPolicy, Settings, WindowIndex},
+    export::ExportFormat,
+};
+
+/// Version string including the `niri-ipc` version the binary was built against.
+pub const LONG_VERSION: &str = concat!(
+    env!("CARGO_PKG_VERSION"),
+    "\nniri-ipc ",
+    env!("NIRI_IPC_VERSION")
+);
+
 #[derive(Clone, Debug, Deserialize, Serialize, Subcommand)]
 pub enum Command {
+    /// Create a starter configuration file.
+    Init {
+        /// Overwrite the configuration file if it already exists.
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Open the configuration file in `$EDITOR`, creating it if missing.
+    Edit {
+        /// Do not validate the configuration file after the editor exits.
+        #[arg(long)]
+        no_validate: bool,
+    },
+
     /// Validate the configuration file.
-    Validate,
+    Validate {
+        /// Also report match rules that do not match any open window.
+        /// Requires a running Niri instance.
+        #[arg(long, verbatim_doc_comment)]
+        strict: bool,
+    },
+
+    /// Validate the configuration file, then ask Niri to reload its own configuration.
+    Reload,
+
+    /// Print the configuration as it is applied, with settings resolved and defaults
+    /// filled in.
+    #[command(verbatim_doc_comment)]
+    Export {
+        /// Output format.
+        #[arg(long, value_enum, default_value_t)]
+        format: ExportFormat,
+    },
+
+    /// Rewrite the configuration file in the current format, keeping a `.bak` copy.
+    Migrate {
+        /// Rewrite the whole file in canonical form, which drops comments, instead of only
+        /// updating `config-version`.
+        #[arg(long)]
+        canonical: bool,
+    },
+
+    /// Check the configuration and the connection to Niri.
+    Doctor,
+
+    /// Run in the background and execute commands sent with `--client`.
+    Daemon {
+        /// Also write the received commands, the actions sent to Niri and the outcome of
+        /// each command to this file.
+        #[arg(long, value_name = "PATH", verbatim_doc_comment)]
+        log_file: Option<PathBuf>,
 
-    /// Launch the specified application.
+        /// Size in bytes beyond which the log file is moved to `<PATH>.1` and started anew.
+        #[arg(long, value_name = "BYTES", default_value_t = 1024 * 1024)]
+        log_max_size: u64,
+    },
+
+    /// Launch the specified applications, one after another.
     Launch {
-        #[arg(value_name = "APP_NAME")]
-        application_name: String,
+        #[arg(value_name = "APP_NAME", required = true)]
+        application_names: Vec<String>,
+
+        /// Wait for a matching window to appear and focus it.
+        #[arg(long)]
+        focus: bool,
+
+        /// Maximum time in milliseconds to wait for the window with `--focus`.
+        /// Defaults to the `launch-timeout-ms` setting, or 5000.
+        #[arg(long, value_name = "MS", requires = "focus", verbatim_doc_comment)]
+        timeout: Option<u64>,
+
+        /// Only launch applications that have no matching window yet.
+        #[arg(long)]
+        if_absent: bool,
+
+        /// Print the resolved command line instead of running it.
+        #[arg(long, conflicts_with = "focus")]
+        print_command: bool,
+
+        /// Warn about applications missing from the config instead of failing, unless
+        /// none of them is found.
+        #[arg(long, verbatim_doc_comment)]
+        ignore_missing: bool,
+
+        /// Run this command instead of the configured `spawn`/`spawn-sh`. Takes all
+        /// remaining arguments, so it must come last.
+        #[arg(
+            long,
+            value_name = "CMD",
+            num_args = 1..,
+            allow_hyphen_values = true,
+            verbatim_doc_comment
+        )]
+        command: Option<Vec<String>>,
     },
 
-    /// Show the specified application window.
+    /// Launch every application marked `autostart`, in the order of the config.
+    Start {
+        /// Only launch this application among those marked `autostart`. Can be given
+        /// several times.
+        #[arg(long, value_name = "APP_NAME", verbatim_doc_comment)]
+        only: Vec<String>,
+    },
+
+    /// Show the windows of the specified applications, one after another.
+    #[command(group = AdHocTarget::required_with("application_names"))]
     Show {
-        #[arg(value_name = "APP_NAME")]
-        application_name: String,
+        #[arg(value_name = "APP_NAME", conflicts_with_all = ["app_id", "title"])]
+        application_names: Vec<String>,
+
+        #[command(flatten)]
+        target: AdHocTarget,
+
+        /// Move the window to the focused workspace without focusing it.
+        #[arg(long)]
+        no_focus: bool,
+
+        /// Move the window to the active workspace of this output instead of the focused
+        /// workspace.
+        #[arg(long, value_name = "OUTPUT", verbatim_doc_comment)]
+        output: Option<String>,
+
+        /// Warn about applications missing from the config instead of failing, unless
+        /// none of them is found.
+        #[arg(long, verbatim_doc_comment)]
+        ignore_missing: bool,
     },
 
     /// Hide the specified application window.
+    #[command(group = AdHocTarget::required_with("application_name"))]
     Hide {
+        #[arg(value_name = "APP_NAME", conflicts_with_all = ["app_id", "title"])]
+        application_name: Option<String>,
+
+        #[command(flatten)]
+        target: AdHocTarget,
+
+        /// Focus the previously focused window after hiding.
+        #[arg(long)]
+        focus_previous: bool,
+
+        /// Switch to the workspace with this name or ID after hiding.
+        #[arg(long, value_name = "NAME", conflicts_with = "focus_previous")]
+        focus_workspace: Option<String>,
+
+        /// Switch back to the previously focused workspace after hiding, if the window was
+        /// the only one on its workspace.
+        #[arg(long, conflicts_with_all = ["focus_previous", "focus_workspace"])]
+        toggle_workspace: bool,
+    },
+
+    /// Focus the specified application window wherever it is, switching workspaces if
+    /// needed.
+    Focus {
         #[arg(value_name = "APP_NAME")]
         application_name: String,
     },
 
-    /// Activate the specified application window.
+    /// Activate the specified application window on the focused workspace.
     Activate {
         #[arg(value_name = "APP_NAME")]
         application_name: String,
+
+        /// Switch to the window's workspace if it is not on the focused workspace,
+        /// instead of failing.
+        #[arg(long, verbatim_doc_comment)]
+        anywhere: bool,
+    },
+
+    /// Close the specified application window.
+    Close {
+        #[arg(value_name = "APP_NAME")]
+        application_name: String,
+
+        /// Close all matched windows instead of requiring a single match.
+        #[arg(long)]
+        all: bool,
+    },
+
+    /// Terminate the process of the specified application window.
+    Kill {
+        #[arg(value_name = "APP_NAME")]
+        application_name: String,
+
+        /// Send SIGKILL instead of SIGTERM.
+        #[arg(long)]
+        force: bool,
     },
 
     /// Toggle the specified application window.
+    #[command(group = AdHocTarget::required_with("application_name"))]
     Toggle {
+        #[arg(value_name = "APP_NAME", conflicts_with_all = ["app_id", "title"])]
+        application_name: Option<String>,
+
+        #[command(flatten)]
+        target: AdHocTarget,
+
+        /// Focus the previously focused window after hiding.
+        #[arg(long)]
+        focus_previous: bool,
+
+        /// Toggle fullscreen instead of hiding the window when it is focused.
+        #[arg(long, conflicts_with = "focus_previous")]
+        fullscreen: bool,
+
+        /// Switch to the workspace of the window, or back to the previous workspace if it is
+        /// already focused, instead of moving the window.
+        #[arg(long, conflicts_with_all = ["focus_previous", "fullscreen"])]
+        workspace: bool,
+
+        /// Switch to the workspace with this name or ID after hiding.
+        #[arg(
+            long,
+            value_name = "NAME",
+            conflicts_with_all = ["focus_previous", "fullscreen", "workspace"]
+        )]
+        focus_workspace: Option<String>,
+    },
+
+    /// Bring the application to the front: launch it and focus its window if it is not
+    /// running, switch to its window elsewhere, and do nothing if it is already focused.
+    #[command(verbatim_doc_comment)]
+    Raise {
         #[arg(value_name = "APP_NAME")]
         application_name: String,
+
+        /// Fail instead of launching the application when no window matches.
+        #[arg(long)]
+        no_launch: bool,
+
+        /// Return right after launching instead of waiting for the window to focus it.
+        #[arg(long, conflicts_with = "no_launch")]
+        no_wait: bool,
+
+        /// Maximum time in milliseconds to wait for the window after launching.
+        /// Defaults to the `launch-timeout-ms` setting, or 5000.
+        #[arg(
+            long,
+            value_name = "MS",
+            conflicts_with_all = ["no_launch", "no_wait"],
+            verbatim_doc_comment
+        )]
+        timeout: Option<u64>,
+
+        /// Fail instead of switching to the workspace of a window elsewhere.
+        #[arg(long)]
+        no_switch: bool,
+    },
+
+    /// List the configured applications and the windows they currently match.
+    List {
+        /// Only list applications that currently match an open window.
+        #[arg(long)]
+        only_running: bool,
     },
+
+    /// List the open windows that an ad-hoc match rule matches.
+    Match {
+        /// Regex the app ID must match.
+        #[arg(long, value_name = "REGEX")]
+        app_id: Option<String>,
+
+        /// Regex the title must match.
+        #[arg(long, value_name = "REGEX")]
+        title: Option<String>,
+
+        /// Select only the N-th matched window by PID, or `first`/`last`.
+        #[arg(long, value_name = "INDEX")]
+        index: Option<String>,
+
+        /// Match the app ID case-sensitively.
+        #[arg(long)]
+        case_sensitive: bool,
+
+        /// Print the matched windows as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List the configured applications whose rules target the window with this ID.
+    Which { window_id: u64 },
+
+    /// List the windows currently open in Niri.
+    Windows {
+        /// Print the windows as JSON.
+        #[arg(long)]
+        json: bool,
+
+        /// Keep running and print the windows that appear, disappear or change.
+        #[arg(long, conflicts_with = "json")]
+        watch: bool,
+    },
+
+    /// Print version information, including the version of the running Niri.
+    Version,
+
+    /// Measure the round-trip time of requests to Niri.
+    Ping {
+        /// Number of requests to send.
+        #[arg(short = 'n', long, default_value_t = 10, value_parser = clap::value_parser!(u32).range(1..))]
+        count: u32,
+    },
+
+    /// List the workspaces in Niri, including hidden ones.
+    Workspaces {
+        /// Print the workspaces as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+impl Command {
+    /// Whether the command targets windows by an ad-hoc match rule instead of a configured
+    /// application.
+    pub fn is_ad_hoc(&self) -> bool {
+        match self {
+            Self::Show { target, .. } | Self::Hide { target, .. } | Self::Toggle { target, .. } => {
+                target.app_id.is_some() || target.title.is_some()
+            }
+            _ => false,
+        }
+    }
+
+    /// Names of the configured applications the command acts on.
+    pub fn application_names(&self) -> Vec<&str> {
+        match self {
+            Self::Launch {
+                application_names, ..
+            }
+            | Self::Show {
+                application_names, ..
+            } => application_names.iter().map(String::as_str).collect(),
+            Self::Hide {
+                application_name, ..
+            }
+            | Self::Toggle {
+                application_name, ..
+            } => application_name.iter().map(String::as_str).collect(),
+            Self::Focus { application_name }
+            | Self::Activate {
+                application_name, ..
+            }
+            | Self::Close {
+                application_name, ..
+            }
+            | Self::Kill {
+                application_name, ..
+            }
+            | Self::Raise {
+                application_name, ..
+            } => vec![application_name],
+            _ => vec![],
+        }
+    }
+}
+
+/// Match properties given on the command line in place of an application name, for
+/// windows that have no application in the config.
+#[derive(Args, Clone, Debug, Default, Deserialize, Serialize)]
+pub struct AdHocTarget {
+    /// Target windows whose app ID matches this regex instead of a configured application.
+    #[arg(long, value_name = "REGEX")]
+    pub app_id: Option<String>,
+
+    /// Target windows whose title matches this regex instead of a configured application.
+    #[arg(long, value_name = "REGEX")]
+    pub title: Option<String>,
+}
+
+impl AdHocTarget {
+    /// Requires either the positional argument `name` or at least one of the properties,
+    /// so that a missing target is reported with all alternatives.
+    fn required_with(name: &'static str) -> ArgGroup {
+        ArgGroup::new("target")
+            .args([name, "app_id", "title"])
+            .required(true)
+            .multiple(true)
+    }
+
+    /// Builds an application with a single match rule from the given properties, selecting
+    /// the window at `index` like `--match-index` does, or returns `None` if none are given.
+    pub fn application(&self, index: Option<WindowIndex>) -> Result<Option<Application>> {
+        if self.app_id.is_none() && self.title.is_none() {
+            return Ok(None);
+        }
+        let mut rule =
+            MatchRule::parse(self.app_id.as_deref(), self.title.as_deref(), None, false)?;
+        rule.index = index;
+
+        Ok(Some(Application {
+            name: "ad-hoc".to_string(),
+            matches: vec![rule],
+            ..Default::default()
+        }))
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, Parser)]
-#[command(about, long_about = None, version)]
+#[command(about, long_about = None, version, long_version = LONG_VERSION)]
 struct CliInner {
     #[command(subcommand)]
     command: Command,
@@ -56,11 +432,92 @@ struct CliInner {
         verbatim_doc_comment
     )]
     config_path: Option<String>,
+
+    /// Format of the configuration file.
+    /// Defaults to the one implied by its extension, or KDL.
+    #[arg(long, value_enum, value_name = "FORMAT", verbatim_doc_comment)]
+    config_format: Option<ConfigFormat>,
+
+    /// Path to the Niri IPC socket.
+    /// Defaults to the value of `$NIRI_SOCKET`.
+    #[arg(long = "socket", value_name = "PATH", verbatim_doc_comment)]
+    socket_path: Option<PathBuf>,
+
+    /// Keep retrying to connect to Niri for up to this many seconds, e.g. when running
+    /// at login before Niri is up.
+    #[arg(long, value_name = "SECONDS", global = true, verbatim_doc_comment)]
+    wait_ready: Option<u64>,
+
+    /// Do not ask for confirmation before acting on multiple windows.
+    #[arg(short = 'y', long, global = true)]
+    yes: bool,
+
+    /// Do not print which windows the command acted on.
+    #[arg(short = 'q', long, global = true)]
+    quiet: bool,
+
+    /// Print only the IDs of the windows the command acted on, one per line.
+    #[arg(long, global = true, conflicts_with = "quiet")]
+    print_id: bool,
+
+    /// Show the output of launched applications instead of discarding it.
+    #[arg(long, global = true)]
+    inherit_io: bool,
+
+    /// Act on the window with the lowest PID when several windows match, instead of
+    /// failing.
+    #[arg(long, global = true, verbatim_doc_comment)]
+    first_match: bool,
+
+    /// Select the window at this position (0-based, `first` or `last`) among the windows
+    /// each match rule matches, replacing the `index` of the configured rules.
+    #[arg(long, value_name = "N", global = true, verbatim_doc_comment)]
+    match_index: Option<WindowIndex>,
+
+    /// Check that Niri applied the actions of `show` and `activate`, and fail if not.
+    #[arg(long, global = true)]
+    verify: bool,
+
+    /// Send the command to a running daemon instead of executing it.
+    #[arg(long, global = true)]
+    client: bool,
+
+    /// Show a desktop notification when the command fails.
+    #[arg(long, global = true)]
+    notify: bool,
+
+    /// Print how long each phase of the command takes to stderr.
+    #[arg(long, global = true)]
+    profile: bool,
+
+    /// Path to the daemon socket.
+    /// Defaults to `$XDG_RUNTIME_DIR/niri-app-hotkey.sock`.
+    #[arg(
+        long = "daemon-socket",
+        value_name = "PATH",
+        global = true,
+        verbatim_doc_comment
+    )]
+    daemon_socket_path: Option<PathBuf>,
 }
 
 pub struct Cli {
     pub command: Command,
     pub config_path: PathBuf,
+    pub config_format: ConfigFormat,
+    pub socket_path: Option<PathBuf>,
+    /// How long to keep retrying to connect to Niri, zero to try only once.
+    pub wait_ready: Duration,
+    pub yes: bool,
+    pub quiet: bool,
+    pub print_id: bool,
+    pub match_index: Option<WindowIndex>,
+    pub client: bool,
+    pub notify: bool,
+    pub profile: bool,
+    pub daemon_socket_path: Option<PathBuf>,
+    /// Settings given on the command line, which take precedence over the config file.
+    pub settings: Settings,
 }
 
 impl Cli {
@@ -73,10 +530,31 @@ impl Cli {
             .map(PathBuf::from)
             .ok_or(())
             .or_else(|_| Self::get_default_config_path())?;
+        let config_format = cli
+            .config_format
+            .unwrap_or_else(|| ConfigFormat::from_path(&config_path));
 
         Ok(Self {
             command,
             config_path,
+            config_format,
+            socket_path: cli.socket_path,
+            wait_ready: Duration::from_secs(cli.wait_ready.unwrap_or_default()),
+            yes: cli.yes,
+            quiet: cli.quiet,
+            print_id: cli.print_id,
+            match_index: cli.match_index,
+            client: cli.client,
+            notify: cli.notify,
+            profile: cli.profile,
+            daemon_socket_path: cli.daemon_socket_path,
+            settings: Settings {
+                inherit_io: cli.inherit_io.then_some(true),
+                // Set as a multi policy, so that it also overrides `multi` in the config
+                multi: cli.first_match.then_some(MultiPolicy::First),
+                verify: cli.verify.then_some(true),
+                ..Default::default()
+            },
         })
     }
 
@@ -1,75 +1,1441 @@
 use std::{
+    cmp::Ordering,
+    fmt::{self, Display},
     fs,
-    path::PathBuf,
+    io::{self, Read},
+    iter, mem,
+    ops::Range,
+    path::Path,
     str::{self, FromStr},
+    time::Duration,
 };
 
-use knus::Decode;
-use miette::{Context, IntoDiagnostic, Result, miette};
-use regex::Regex as OriginalRegex;
+use knus::{
+    Decode,
+    ast::{Literal, TypeName},
+    decode::Context as DecodeContext,
+    errors::DecodeError,
+    span::Spanned,
+    traits::{DecodeScalar, ErrorSpan},
+};
+use miette::{
+    Context, Diagnostic, IntoDiagnostic, LabeledSpan, NamedSource, Report, Result, bail, miette,
+};
+use regex::{Regex as OriginalRegex, RegexBuilder};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+use thiserror::Error;
+
+use crate::error::AppError;
 
+/// A regular expression. Patterns decoded from the config file are compiled afterwards by
+/// [`Config::from_str`], so that every invalid pattern can be reported at once.
 #[derive(Clone, Debug)]
-pub struct Regex(pub OriginalRegex);
+pub struct Regex {
+    pattern: String,
+    compiled: Option<OriginalRegex>,
+}
+
+impl Regex {
+    fn uncompiled(pattern: String) -> Self {
+        Self {
+            pattern,
+            compiled: None,
+        }
+    }
+
+    fn compile(&mut self, case_insensitive: bool) -> Result<(), regex::Error> {
+        let regex = RegexBuilder::new(&self.pattern)
+            .case_insensitive(case_insensitive)
+            .build()?;
+        self.compiled = Some(regex);
+
+        Ok(())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Returns whether the pattern matches `text`. A pattern that was never compiled does
+    /// not match anything.
+    pub fn is_match(&self, text: &str) -> bool {
+        self.compiled
+            .as_ref()
+            .is_some_and(|regex| regex.is_match(text))
+    }
+}
+
 impl FromStr for Regex {
-    type Err = <OriginalRegex as FromStr>::Err;
+    type Err = regex::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        OriginalRegex::from_str(s).map(Self)
+        let mut regex = Self::uncompiled(s.to_string());
+        regex.compile(false)?;
+
+        Ok(regex)
     }
 }
 
-#[derive(Clone, Debug, Decode)]
+impl<S: ErrorSpan> DecodeScalar<S> for Regex {
+    fn type_check(type_name: &Option<Spanned<TypeName, S>>, ctx: &mut DecodeContext<S>) {
+        <String as DecodeScalar<S>>::type_check(type_name, ctx);
+    }
+
+    fn raw_decode(
+        value: &Spanned<Literal, S>,
+        ctx: &mut DecodeContext<S>,
+    ) -> Result<Self, DecodeError<S>> {
+        <String as DecodeScalar<S>>::raw_decode(value, ctx).map(Self::uncompiled)
+    }
+}
+
+impl<'de> Deserialize<'de> for Regex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::uncompiled)
+    }
+}
+
+impl Serialize for Regex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.pattern)
+    }
+}
+
+/// A pattern in the config file that failed to compile.
+#[derive(Debug, Error, Diagnostic)]
+#[error("{0}")]
+struct InvalidPattern(String);
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("Found {} invalid pattern(s) in the configuration", .0.len())]
+struct InvalidPatterns(#[related] Vec<InvalidPattern>);
+
+/// Position of the window to select from the sorted candidates of a match rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowIndex {
+    First,
+    Last,
+    Nth(usize),
+}
+
+impl WindowIndex {
+    /// Resolves the index against a candidate list of the given length.
+    pub fn resolve(self, len: usize) -> Option<usize> {
+        match self {
+            Self::First => (len > 0).then_some(0),
+            Self::Last => len.checked_sub(1),
+            Self::Nth(index) => (index < len).then_some(index),
+        }
+    }
+}
+
+impl FromStr for WindowIndex {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first" => Ok(Self::First),
+            "last" => Ok(Self::Last),
+            _ => s
+                .parse()
+                .map(Self::Nth)
+                .map_err(|_| format!("invalid index '{s}', expected a number, 'first' or 'last'")),
+        }
+    }
+}
+
+impl Display for WindowIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::First => write!(f, "first"),
+            Self::Last => write!(f, "last"),
+            Self::Nth(index) => write!(f, "{index}"),
+        }
+    }
+}
+
+impl<S: ErrorSpan> DecodeScalar<S> for WindowIndex {
+    fn type_check(type_name: &Option<Spanned<TypeName, S>>, ctx: &mut DecodeContext<S>) {
+        <usize as DecodeScalar<S>>::type_check(type_name, ctx);
+    }
+
+    fn raw_decode(
+        value: &Spanned<Literal, S>,
+        ctx: &mut DecodeContext<S>,
+    ) -> Result<Self, DecodeError<S>> {
+        match &**value {
+            Literal::String(s) => s.parse().map_err(|err| DecodeError::conversion(value, err)),
+            _ => <usize as DecodeScalar<S>>::raw_decode(value, ctx).map(Self::Nth),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for WindowIndex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawIndex {
+            Nth(usize),
+            Keyword(String),
+        }
+
+        match RawIndex::deserialize(deserializer)? {
+            RawIndex::Nth(index) => Ok(Self::Nth(index)),
+            RawIndex::Keyword(keyword) => keyword.parse().map_err(de::Error::custom),
+        }
+    }
+}
+
+impl Serialize for WindowIndex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::First => serializer.serialize_str("first"),
+            Self::Last => serializer.serialize_str("last"),
+            Self::Nth(index) => serializer.serialize_u64(*index as u64),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+pub enum ConfigFormat {
+    Kdl,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Detects the format from the file extension, falling back to KDL.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::Toml,
+            Some("yaml" | "yml") => Self::Yaml,
+            _ => Self::Kdl,
+        }
+    }
+
+    /// Returns a commented starter configuration in this format.
+    pub fn template(self) -> &'static str {
+        match self {
+            Self::Kdl => include_str!("templates/config.kdl"),
+            Self::Toml => include_str!("templates/config.toml"),
+            Self::Yaml => include_str!("templates/config.yaml"),
+        }
+    }
+}
+
+/// How a launched application is placed relative to the hotkey process.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, knus::DecodeScalar, Deserialize, Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum SpawnScope {
+    /// Spawn the application as a plain child process.
+    #[default]
+    None,
+    /// Spawn the application in its own transient unit via `systemd-run --user --scope`.
+    Systemd,
+}
+
+/// What `toggle` does when the matched window is already focused.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, knus::DecodeScalar, Deserialize, Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnFocused {
+    /// Move the window to the hidden workspace.
+    #[default]
+    Hide,
+    /// Launch another instance of the application.
+    Launch,
+    /// Leave the window as it is.
+    Nothing,
+}
+
+/// What commands do when the rules of an application match several windows.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, knus::DecodeScalar, Deserialize, Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum MultiPolicy {
+    /// Fail and list the matched windows.
+    #[default]
+    Error,
+    /// Act on the window with the lowest PID.
+    First,
+    /// Act on every matched window where the command supports it, and fail otherwise.
+    All,
+}
+
+/// How the properties of a match rule are combined.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, knus::DecodeScalar, Deserialize, Serialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum RequireMode {
+    /// A window must match every property set on the rule.
+    #[default]
+    All,
+    /// A window must match at least one property set on the rule.
+    Any,
+}
+
+#[derive(Clone, Debug, Default, Decode, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct MatchRule {
-    #[knus(property, str)]
+    #[knus(property)]
+    pub id: Option<u64>,
+    #[knus(property)]
     pub app_id: Option<Regex>,
-    #[knus(property, str)]
+    #[knus(property)]
     pub title: Option<Regex>,
     #[knus(property)]
-    pub index: Option<usize>,
+    pub is_focused: Option<bool>,
+    #[knus(property)]
+    pub is_floating: Option<bool>,
+    #[knus(property)]
+    pub is_urgent: Option<bool>,
+    #[knus(property)]
+    pub index: Option<WindowIndex>,
+    #[knus(property)]
+    pub limit: Option<usize>,
+    /// Bounds on the window size in logical pixels, which always apply regardless of
+    /// `require`.
+    #[knus(property)]
+    pub min_width: Option<u32>,
+    #[knus(property)]
+    pub max_width: Option<u32>,
+    #[knus(property)]
+    pub min_height: Option<u32>,
+    #[knus(property)]
+    pub max_height: Option<u32>,
+    #[knus(property, default)]
+    #[serde(default)]
+    pub case_sensitive: bool,
+    #[knus(property, default)]
+    #[serde(default)]
+    pub require: RequireMode,
 }
 
-#[derive(Clone, Debug, Decode)]
+impl MatchRule {
+    /// Builds a rule from its textual properties, as written in the config file.
+    pub fn parse(
+        app_id: Option<&str>,
+        title: Option<&str>,
+        index: Option<&str>,
+        case_sensitive: bool,
+    ) -> Result<Self> {
+        let pattern =
+            |pattern: Option<&str>| pattern.map(|pattern| Regex::uncompiled(pattern.to_string()));
+        let mut rule = MatchRule {
+            app_id: pattern(app_id),
+            title: pattern(title),
+            index: index
+                .map(WindowIndex::from_str)
+                .transpose()
+                .map_err(|err| miette!("Invalid index: {err}"))?,
+            case_sensitive,
+            ..Default::default()
+        };
+        let errors = rule.compile_patterns();
+        if !errors.is_empty() {
+            bail!("{}", errors.join("\n"));
+        }
+
+        Ok(rule)
+    }
+
+    /// Compiles the rule's patterns, matching `app_id` case-insensitively unless the rule
+    /// opts out via `case_sensitive`. Returns a message for each invalid pattern.
+    fn compile_patterns(&mut self) -> Vec<String> {
+        let case_insensitive = !self.case_sensitive;
+        [
+            ("app-id", self.app_id.as_mut(), case_insensitive),
+            ("title", self.title.as_mut(), false),
+        ]
+        .into_iter()
+        .filter_map(|(property, regex, case_insensitive)| {
+            let regex = regex?;
+            let err = regex.compile(case_insensitive).err()?;
+            Some(format!(
+                "invalid `{property}` pattern {:?}: {err}",
+                regex.as_str()
+            ))
+        })
+        .collect()
+    }
+}
+
+/// An alternative set of rules for an application. Groups are tried in order after the
+/// application's own rules, and the first one that targets a window wins.
+#[derive(Clone, Debug, Default, Decode, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct MatchGroup {
+    #[knus(children(name = "match"))]
+    #[serde(rename = "match", default)]
+    pub matches: Vec<MatchRule>,
+    #[knus(children(name = "exclude"))]
+    #[serde(rename = "exclude", default)]
+    pub excludes: Vec<MatchRule>,
+}
+
+/// Named rules that applications can reuse with `match-ref` and `exclude-ref`, instead of
+/// repeating them.
+#[derive(Clone, Debug, Default, Decode, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct MatchSet {
+    #[knus(argument)]
+    pub name: String,
+    #[knus(children(name = "rule"))]
+    #[serde(rename = "rule", default)]
+    pub rules: Vec<MatchRule>,
+}
+
+#[derive(Clone, Debug, Default, Decode, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct Application {
     #[knus(argument)]
     pub name: String,
+    /// Free-form note shown by `list`, which does not affect matching.
+    #[knus(child, unwrap(argument))]
+    pub description: Option<String>,
     #[knus(child, unwrap(arguments))]
     pub spawn: Option<Vec<String>>,
+    /// Alternative to `spawn` as a single string, which is split into `spawn` arguments
+    /// with shell-like quoting when the config is parsed.
+    #[knus(child, unwrap(argument))]
+    pub spawn_line: Option<String>,
     #[knus(child, unwrap(argument))]
     pub spawn_sh: Option<String>,
+    #[knus(child, unwrap(argument), default)]
+    #[serde(default)]
+    pub spawn_scope: SpawnScope,
+    #[knus(child, unwrap(argument), default)]
+    #[serde(default)]
+    pub on_focused: OnFocused,
+    /// Whether `start` launches the application, e.g. at login.
+    #[knus(child, unwrap(argument), default)]
+    #[serde(default)]
+    pub autostart: bool,
     #[knus(children(name = "match"))]
+    #[serde(rename = "match", default)]
     pub matches: Vec<MatchRule>,
     #[knus(children(name = "exclude"))]
+    #[serde(rename = "exclude", default)]
     pub excludes: Vec<MatchRule>,
+    /// Names of match sets whose rules are added to `matches` when the config is parsed.
+    #[knus(children(name = "match-ref"), unwrap(argument))]
+    #[serde(rename = "match-ref", default, skip_serializing_if = "Vec::is_empty")]
+    pub match_refs: Vec<String>,
+    /// Names of match sets whose rules are added to `excludes` when the config is parsed.
+    #[knus(children(name = "exclude-ref"), unwrap(argument))]
+    #[serde(rename = "exclude-ref", default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_refs: Vec<String>,
+    #[knus(children(name = "group"))]
+    #[serde(rename = "group", default)]
+    pub groups: Vec<MatchGroup>,
+    #[knus(child, default)]
+    #[serde(default)]
+    pub settings: Settings,
+}
+
+impl Application {
+    /// Returns the `(matches, excludes)` rule sets to try in order: the application's own
+    /// rules first, then each group.
+    pub fn rule_sets(&self) -> impl Iterator<Item = (&[MatchRule], &[MatchRule])> {
+        iter::once((self.matches.as_slice(), self.excludes.as_slice())).chain(
+            self.groups
+                .iter()
+                .map(|group| (group.matches.as_slice(), group.excludes.as_slice())),
+        )
+    }
+}
+
+/// Version of the config schema this build understands, see [`Config::version_warning`].
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Config path that reads the config from stdin instead of a file.
+const STDIN_PATH: &str = "-";
+
+/// Name of the config read from stdin in diagnostics.
+const STDIN_NAME: &str = "<stdin>";
+
+/// Checks whether `path` stands for stdin rather than a config file.
+pub fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == STDIN_PATH
+}
+
+const DEFAULT_SHELL: &str = "sh";
+const DEFAULT_LAUNCH_TIMEOUT_MS: u64 = 5000;
+const DEFAULT_AUTOSTART_DELAY_MS: u64 = 0;
+
+/// Options that can be set globally and overridden per application. Unset values fall back
+/// to the global settings, then to built-in defaults.
+#[derive(Clone, Debug, Default, Decode, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Settings {
+    /// Shell used to run `spawn-sh` commands.
+    #[knus(child, unwrap(argument))]
+    pub shell: Option<String>,
+    /// Time to wait for a launched application's window to appear.
+    #[knus(child, unwrap(argument))]
+    pub launch_timeout_ms: Option<u64>,
+    /// Pattern for the name of the workspace that hidden windows are moved to. Takes
+    /// precedence over workspaces flagged as hidden by Niri.
+    #[knus(child, unwrap(argument))]
+    pub hidden_workspace: Option<Regex>,
+    /// Time to keep re-matching windows before giving up when nothing matches.
+    #[knus(child, unwrap(argument))]
+    pub rematch_timeout_ms: Option<u64>,
+    /// Whether launched applications inherit our stdio instead of having it discarded.
+    #[knus(child, unwrap(argument))]
+    pub inherit_io: Option<bool>,
+    /// Whether floating windows are made floating again after being hidden or shown.
+    #[knus(child, unwrap(argument))]
+    pub preserve_floating: Option<bool>,
+    /// Whether the window with the lowest PID is picked when several windows match,
+    /// instead of failing.
+    #[knus(child, unwrap(argument))]
+    pub first_match: Option<bool>,
+    /// Whether focusing a window is checked afterwards and retried once if it did not take.
+    #[knus(child, unwrap(argument))]
+    pub verify_focus: Option<bool>,
+    /// Whether `show` and `activate` check afterwards that Niri applied their actions, and
+    /// fail if not.
+    #[knus(child, unwrap(argument))]
+    pub verify: Option<bool>,
+    /// Time after an application acted during which further commands for it are ignored.
+    #[knus(child, unwrap(argument))]
+    pub cooldown_ms: Option<u64>,
+    /// Time `start` waits before launching the application, after launching the previous one.
+    #[knus(child, unwrap(argument))]
+    pub autostart_delay_ms: Option<u64>,
+    /// What to do when several windows match. Takes precedence over `first_match`.
+    #[knus(child, unwrap(argument))]
+    pub multi: Option<MultiPolicy>,
+}
+
+impl Settings {
+    /// Returns these settings with unset values taken from `fallback`. `multi` and its
+    /// shortcut `first_match` are merged as one, so that the more specific of them wins.
+    pub fn or(&self, fallback: &Settings) -> Settings {
+        Settings {
+            shell: self.shell.clone().or_else(|| fallback.shell.clone()),
+            launch_timeout_ms: self.launch_timeout_ms.or(fallback.launch_timeout_ms),
+            hidden_workspace: self
+                .hidden_workspace
+                .clone()
+                .or_else(|| fallback.hidden_workspace.clone()),
+            rematch_timeout_ms: self.rematch_timeout_ms.or(fallback.rematch_timeout_ms),
+            inherit_io: self.inherit_io.or(fallback.inherit_io),
+            preserve_floating: self.preserve_floating.or(fallback.preserve_floating),
+            first_match: self.first_match.or(fallback.first_match),
+            verify_focus: self.verify_focus.or(fallback.verify_focus),
+            verify: self.verify.or(fallback.verify),
+            cooldown_ms: self.cooldown_ms.or(fallback.cooldown_ms),
+            autostart_delay_ms: self.autostart_delay_ms.or(fallback.autostart_delay_ms),
+            multi: self.own_multi().or(fallback.own_multi()),
+        }
+    }
+
+    pub fn shell(&self) -> &str {
+        self.shell.as_deref().unwrap_or(DEFAULT_SHELL)
+    }
+
+    pub fn launch_timeout(&self) -> Duration {
+        Duration::from_millis(self.launch_timeout_ms.unwrap_or(DEFAULT_LAUNCH_TIMEOUT_MS))
+    }
+
+    pub fn rematch_timeout(&self) -> Option<Duration> {
+        self.rematch_timeout_ms.map(Duration::from_millis)
+    }
+
+    pub fn inherit_io(&self) -> bool {
+        self.inherit_io.unwrap_or(false)
+    }
+
+    pub fn preserve_floating(&self) -> bool {
+        self.preserve_floating.unwrap_or(false)
+    }
+
+    pub fn verify_focus(&self) -> bool {
+        self.verify_focus.unwrap_or(false)
+    }
+
+    pub fn verify(&self) -> bool {
+        self.verify.unwrap_or(false)
+    }
+
+    pub fn multi(&self) -> MultiPolicy {
+        self.own_multi().unwrap_or(MultiPolicy::Error)
+    }
+
+    /// The policy set by `multi`, or else by `first_match`, at this level alone.
+    fn own_multi(&self) -> Option<MultiPolicy> {
+        self.multi.or(self.first_match.map(|first_match| {
+            if first_match {
+                MultiPolicy::First
+            } else {
+                MultiPolicy::Error
+            }
+        }))
+    }
+
+    pub fn cooldown(&self) -> Option<Duration> {
+        self.cooldown_ms.map(Duration::from_millis)
+    }
+
+    pub fn autostart_delay(&self) -> Duration {
+        Duration::from_millis(
+            self.autostart_delay_ms
+                .unwrap_or(DEFAULT_AUTOSTART_DELAY_MS),
+        )
+    }
+
+    /// Returns these settings with every value that has a built-in default filled in.
+    pub fn with_defaults(&self) -> Settings {
+        Settings {
+            shell: Some(self.shell().to_string()),
+            launch_timeout_ms: Some(self.launch_timeout_ms.unwrap_or(DEFAULT_LAUNCH_TIMEOUT_MS)),
+            inherit_io: Some(self.inherit_io()),
+            preserve_floating: Some(self.preserve_floating()),
+            // Written as `multi` alone, which `first_match` is a shortcut for
+            first_match: None,
+            multi: Some(self.multi()),
+            verify_focus: Some(self.verify_focus()),
+            verify: Some(self.verify()),
+            autostart_delay_ms: Some(self.autostart_delay().as_millis() as u64),
+            ..self.clone()
+        }
+    }
 }
 
-#[derive(Clone, Debug, Decode)]
+#[derive(Clone, Debug, Default, Decode, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
+    /// Schema version the config was written for. Unset in configs that predate it.
+    #[knus(child, unwrap(argument))]
+    #[serde(
+        rename = "config-version",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub config_version: Option<u32>,
+    #[knus(child, default)]
+    #[serde(default)]
+    pub settings: Settings,
     #[knus(children(name = "application"))]
+    #[serde(rename = "application", default)]
     pub applications: Vec<Application>,
+    /// Inlined into the applications that reference them when the config is parsed.
+    #[knus(children(name = "match-set"))]
+    #[serde(rename = "match-set", default, skip_serializing_if = "Vec::is_empty")]
+    pub match_sets: Vec<MatchSet>,
+}
+
+/// Builds a diagnostic pointing into the config source for serde-based formats.
+fn source_error(
+    file_name: &str,
+    text: &str,
+    message: impl Display,
+    span: Option<Range<usize>>,
+) -> Report {
+    let labels = span
+        .map(|span| vec![LabeledSpan::at(span, "here")])
+        .unwrap_or_default();
+
+    miette!(labels = labels, "{message}")
+        .with_source_code(NamedSource::new(file_name, text.to_string()))
 }
 
 impl Config {
-    pub fn parse(path: &PathBuf) -> Result<Self> {
-        let file_name = path
-            .as_os_str()
-            .to_str()
-            .ok_or_else(|| miette!("Invalid config file name"))?;
-        let text = fs::read_to_string(path)
+    /// Writes a starter configuration in `format` to `path`. Refuses to overwrite an
+    /// existing file unless `force` is set.
+    pub fn init(path: &Path, format: ConfigFormat, force: bool) -> Result<()> {
+        if is_stdin(path) {
+            bail!("Cannot write the config to stdin, pass a file with --config");
+        }
+        if path.exists() && !force {
+            bail!("Config file already exists at: {path:?}");
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .into_diagnostic()
+                .context(format!("Failed to create config directory at: {parent:?}"))?;
+        }
+        fs::write(path, format.template())
+            .into_diagnostic()
+            .context(format!("Failed to write config file at: {path:?}"))
+    }
+
+    /// Parses the config file at `path`, in the format implied by its extension.
+    pub fn parse(path: &Path) -> Result<Self> {
+        Self::parse_as(path, ConfigFormat::from_path(path))
+    }
+
+    /// Parses the config file at `path` in `format`, whatever its extension. A `path` of
+    /// `-` reads the config from stdin.
+    pub fn parse_as(path: &Path, format: ConfigFormat) -> Result<Self> {
+        let config = if is_stdin(path) {
+            Self::from_stdin(io::stdin(), format)?
+        } else {
+            let file_name = path
+                .as_os_str()
+                .to_str()
+                .ok_or_else(|| miette!("Invalid config file name"))?;
+            let text = fs::read_to_string(path)
+                .into_diagnostic()
+                .context(format!("Failed to read config file at: {path:?}"))?;
+            Self::from_str_as(file_name, &text, format)
+                .context(format!("Failed to parse config file at: {path:?}"))?
+        };
+        if let Some(warning) = config.version_warning() {
+            eprintln!("Warning: {warning}");
+        }
+
+        Ok(config)
+    }
+
+    /// Parses the config given on stdin, read from `stdin` to the end, in `format`.
+    fn from_stdin(mut stdin: impl Read, format: ConfigFormat) -> Result<Self> {
+        let mut text = String::new();
+        stdin
+            .read_to_string(&mut text)
             .into_diagnostic()
-            .context(format!("Failed to read config file at: {path:?}"))?;
+            .context("Failed to read config from stdin")?;
+        if text.trim().is_empty() {
+            bail!("No config given on stdin, pipe one in or pass a file with --config");
+        }
+
+        Self::from_str_as(STDIN_NAME, &text, format).context("Failed to parse stdin")
+    }
 
-        knus::parse(file_name, &text).context(format!("Failed to parse config file at: {path:?}"))
+    /// Describes how `config-version` differs from [`CONFIG_VERSION`], if it does. A config
+    /// without a version is taken to be current.
+    pub fn version_warning(&self) -> Option<String> {
+        let version = self.config_version?;
+        match version.cmp(&CONFIG_VERSION) {
+            Ordering::Less => Some(format!(
+                "config-version {version} is older than version {CONFIG_VERSION} of this \
+                 niri-app-hotkey, see the README for options that changed since"
+            )),
+            Ordering::Greater => Some(format!(
+                "config-version {version} is newer than version {CONFIG_VERSION} of this \
+                 niri-app-hotkey, some options may not be supported, consider upgrading"
+            )),
+            Ordering::Equal => None,
+        }
     }
 
+    /// Parses a config from `text` without touching the file system. `name` is used in
+    /// error messages and its extension selects the format, like the config file name does.
+    pub fn from_str(name: &str, text: &str) -> Result<Self> {
+        Self::from_str_as(name, text, ConfigFormat::from_path(Path::new(name)))
+    }
+
+    /// Parses a config from `text` in `format`. `name` is only used in error messages.
+    pub fn from_str_as(name: &str, text: &str, format: ConfigFormat) -> Result<Self> {
+        let mut config = Self::decode(name, text, format)?;
+        config.split_spawn_lines()?;
+        config.inline_match_sets()?;
+
+        Ok(config)
+    }
+
+    /// Parses a config like [`Config::from_str_as`], but keeps match sets and the
+    /// references to them as they are written instead of inlining them.
+    pub fn decode(name: &str, text: &str, format: ConfigFormat) -> Result<Self> {
+        let mut config: Self = match format {
+            ConfigFormat::Kdl => knus::parse(name, text).map_err(Report::new),
+            ConfigFormat::Toml => toml::from_str(text)
+                .map_err(|err| source_error(name, text, err.message(), err.span())),
+            ConfigFormat::Yaml => serde_yaml::from_str(text).map_err(|err| {
+                let span = err
+                    .location()
+                    .map(|location| location.index()..location.index());
+                source_error(name, text, &err, span)
+            }),
+        }?;
+        config.compile_patterns()?;
+
+        Ok(config)
+    }
+
+    /// Turns each `spawn-line` into `spawn` arguments, so that launching does not need to
+    /// know about it.
+    fn split_spawn_lines(&mut self) -> Result<()> {
+        let mut errors = vec![];
+        for application in &mut self.applications {
+            let Some(line) = application.spawn_line.take() else {
+                continue;
+            };
+            let name = &application.name;
+            if application.spawn.is_some() {
+                errors.push(format!(
+                    "Application '{name}' sets both spawn and spawn-line"
+                ));
+                continue;
+            }
+            match shlex::split(&line) {
+                Some(args) if args.is_empty() => {
+                    errors.push(format!("spawn-line of application '{name}' is empty"));
+                }
+                Some(args) => application.spawn = Some(args),
+                None => errors.push(format!(
+                    "Invalid spawn-line of application '{name}': unterminated quote or trailing backslash"
+                )),
+            }
+        }
+
+        if !errors.is_empty() {
+            bail!(AppError::ConfigInvalid(errors.join("\n")));
+        }
+
+        Ok(())
+    }
+
+    /// Adds the rules of the match sets referenced by each application to its own rules, so
+    /// that matching does not need to know about match sets.
+    fn inline_match_sets(&mut self) -> Result<()> {
+        let match_sets = mem::take(&mut self.match_sets);
+        let mut errors = vec![];
+        for application in &mut self.applications {
+            let references = [
+                (
+                    mem::take(&mut application.match_refs),
+                    &mut application.matches,
+                ),
+                (
+                    mem::take(&mut application.exclude_refs),
+                    &mut application.excludes,
+                ),
+            ];
+            for (names, rules) in references {
+                for name in names {
+                    match match_sets.iter().find(|match_set| match_set.name == name) {
+                        Some(match_set) => rules.extend(match_set.rules.iter().cloned()),
+                        None => errors.push(format!(
+                            "Application '{}' references unknown match-set '{name}'",
+                            application.name
+                        )),
+                    }
+                }
+            }
+        }
+
+        if !errors.is_empty() {
+            bail!(AppError::ConfigInvalid(errors.join("\n")));
+        }
+
+        Ok(())
+    }
+
+    /// Compiles every pattern in the config, reporting all invalid ones together along with
+    /// where they are, instead of stopping at the first.
+    fn compile_patterns(&mut self) -> Result<()> {
+        fn compile_hidden_workspace(settings: &mut Settings) -> Option<String> {
+            let regex = settings.hidden_workspace.as_mut()?;
+            let err = regex.compile(false).err()?;
+            Some(format!(
+                "invalid `hidden-workspace` pattern {:?}: {err}",
+                regex.as_str()
+            ))
+        }
+
+        let mut errors = vec![];
+        if let Some(message) = compile_hidden_workspace(&mut self.settings) {
+            errors.push(format!("settings: {message}"));
+        }
+        for match_set in &mut self.match_sets {
+            for (index, rule) in match_set.rules.iter_mut().enumerate() {
+                errors.extend(rule.compile_patterns().into_iter().map(|message| {
+                    format!(
+                        "rule #{} of match-set '{}': {message}",
+                        index + 1,
+                        match_set.name
+                    )
+                }));
+            }
+        }
+        for application in &mut self.applications {
+            let name = &application.name;
+            if let Some(message) = compile_hidden_workspace(&mut application.settings) {
+                errors.push(format!("settings of application '{name}': {message}"));
+            }
+
+            let own_rules = [
+                ("match", None, &mut application.matches),
+                ("exclude", None, &mut application.excludes),
+            ];
+            let group_rules =
+                application
+                    .groups
+                    .iter_mut()
+                    .enumerate()
+                    .flat_map(|(index, group)| {
+                        [
+                            ("match", Some(index), &mut group.matches),
+                            ("exclude", Some(index), &mut group.excludes),
+                        ]
+                    });
+            for (kind, group_index, rules) in own_rules.into_iter().chain(group_rules) {
+                let group = group_index
+                    .map(|group_index| format!(" of group #{}", group_index + 1))
+                    .unwrap_or_default();
+                for (index, rule) in rules.iter_mut().enumerate() {
+                    errors.extend(rule.compile_patterns().into_iter().map(|message| {
+                        format!(
+                            "{kind} rule #{}{group} of application '{name}': {message}",
+                            index + 1
+                        )
+                    }));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(InvalidPatterns(errors.into_iter().map(InvalidPattern).collect()).into())
+        }
+    }
+
+    /// Returns the config as it is applied: every application's settings are resolved
+    /// against the global settings, and built-in defaults are filled in.
+    pub fn resolved(&self) -> Config {
+        Config {
+            config_version: self.config_version,
+            settings: self.settings.with_defaults(),
+            applications: self
+                .applications
+                .iter()
+                .map(|application| Application {
+                    settings: self.settings_for(application).with_defaults(),
+                    ..application.clone()
+                })
+                .collect(),
+            match_sets: self.match_sets.clone(),
+        }
+    }
+
+    /// Returns the config with the `index` of every match rule replaced by `index`, e.g. to
+    /// pick another one of several matching windows for a single command.
+    pub fn with_match_index(&self, index: WindowIndex) -> Config {
+        let mut config = self.clone();
+        for application in &mut config.applications {
+            let group_matches = application
+                .groups
+                .iter_mut()
+                .flat_map(|group| &mut group.matches);
+            for rule in application.matches.iter_mut().chain(group_matches) {
+                rule.index = Some(index);
+            }
+        }
+
+        config
+    }
+
+    /// Resolves the effective settings of an application against the global settings.
+    pub fn settings_for(&self, application: &Application) -> Settings {
+        application.settings.or(&self.settings)
+    }
+
+    /// Looks up an application by its exact name, or else by a case-insensitive prefix of
+    /// its name that only one application has.
     pub fn find_application(&self, name: &str) -> Result<&Application> {
-        self.applications
+        if self.applications.is_empty() {
+            bail!(AppError::ConfigInvalid(format!(
+                "Application with name '{name}' not found, because no applications are defined in the configuration. Run `niri-app-hotkey init --force` to create a starter config."
+            )));
+        }
+
+        if let Some(application) = self.applications.iter().find(|app| app.name == name) {
+            return Ok(application);
+        }
+
+        let prefix = name.to_lowercase();
+        let candidates = self
+            .applications
             .iter()
-            .find(|app| app.name == name)
-            .ok_or_else(|| {
-                miette!(
-                    "Application with name '{}' not found in configuration.",
-                    name
+            .filter(|app| app.name.to_lowercase().starts_with(&prefix))
+            .collect::<Vec<_>>();
+        match candidates.as_slice() {
+            [application] => Ok(application),
+            [] => bail!(
+                "Application with name '{}' not found in configuration.",
+                name
+            ),
+            _ => {
+                let names = candidates
+                    .iter()
+                    .map(|app| format!("'{}'", app.name))
+                    .collect::<Vec<_>>();
+                bail!(
+                    "Application name '{name}' is ambiguous, it is a prefix of {}.",
+                    names.join(", ")
                 )
+            }
+        }
+    }
+
+    /// Returns the applications marked `autostart` in config order, or with `only`, just
+    /// the named ones among them.
+    pub fn autostart_applications(&self, only: &[String]) -> Result<Vec<&Application>> {
+        let autostart = self
+            .applications
+            .iter()
+            .filter(|app| app.autostart)
+            .collect::<Vec<_>>();
+        let mut selected = vec![];
+        for name in only {
+            let application = self.find_application(name)?;
+            if !application.autostart {
+                let names = autostart
+                    .iter()
+                    .map(|app| format!("'{}'", app.name))
+                    .collect::<Vec<_>>();
+                bail!(
+                    "Application '{}' is not marked `autostart`, applications marked are: {}",
+                    application.name,
+                    if names.is_empty() {
+                        "none".to_string()
+                    } else {
+                        names.join(", ")
+                    }
+                );
+            }
+            selected.push(application.name.as_str());
+        }
+
+        Ok(autostart
+            .into_iter()
+            .filter(|app| only.is_empty() || selected.contains(&app.name.as_str()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn application_settings_override_global_settings() {
+        let config = Config {
+            settings: Settings {
+                shell: Some("bash".to_string()),
+                launch_timeout_ms: Some(1000),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let application = Application {
+            settings: Settings {
+                launch_timeout_ms: Some(2000),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let settings = config.settings_for(&application);
+
+        assert_eq!(settings.shell(), "bash");
+        assert_eq!(settings.launch_timeout(), Duration::from_millis(2000));
+        assert!(settings.hidden_workspace.is_none());
+    }
+
+    #[test]
+    fn settings_fall_back_to_defaults() {
+        let settings = Settings::default();
+
+        assert_eq!(settings.shell(), DEFAULT_SHELL);
+        assert_eq!(
+            settings.launch_timeout(),
+            Duration::from_millis(DEFAULT_LAUNCH_TIMEOUT_MS)
+        );
+    }
+
+    #[test]
+    fn parsed_match_rule_folds_app_id_case() {
+        let rule = MatchRule::parse(Some("^firefox$"), None, Some("last"), false).unwrap();
+
+        assert!(rule.app_id.unwrap().is_match("Firefox"));
+        assert!(matches!(rule.index, Some(WindowIndex::Last)));
+        assert!(MatchRule::parse(None, None, Some("second"), false).is_err());
+    }
+
+    #[test]
+    fn find_application_explains_empty_config() {
+        let config = Config::default();
+
+        let err = config.find_application("Firefox").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(AppError::ConfigInvalid(message)) if message.contains("no applications")
+        ));
+    }
+
+    #[test]
+    fn find_application_falls_back_to_unique_prefix() {
+        let application = |name: &str| Application {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let config = Config {
+            applications: vec![
+                application("Firefox"),
+                application("Firefox Private"),
+                application("Kitty"),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(config.find_application("kit").unwrap().name, "Kitty");
+        assert_eq!(config.find_application("Firefox").unwrap().name, "Firefox");
+        assert_eq!(
+            config.find_application("fire").unwrap_err().to_string(),
+            "Application name 'fire' is ambiguous, it is a prefix of 'Firefox', 'Firefox Private'."
+        );
+        assert_eq!(
+            config.find_application("chrome").unwrap_err().to_string(),
+            "Application with name 'chrome' not found in configuration."
+        );
+    }
+
+    #[test]
+    fn settings_or_lets_the_more_specific_multi_policy_win() {
+        let global = Settings {
+            multi: Some(MultiPolicy::Error),
+            ..Default::default()
+        };
+        let application = Settings {
+            first_match: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(application.or(&global).multi(), MultiPolicy::First);
+
+        let global = Settings {
+            first_match: Some(true),
+            ..Default::default()
+        };
+        let application = Settings {
+            multi: Some(MultiPolicy::All),
+            ..Default::default()
+        };
+        assert_eq!(application.or(&global).multi(), MultiPolicy::All);
+        assert_eq!(Settings::default().or(&global).multi(), MultiPolicy::First);
+    }
+
+    #[test]
+    fn autostart_applications_keep_config_order() {
+        let application = |name: &str, autostart: bool| Application {
+            name: name.to_string(),
+            autostart,
+            ..Default::default()
+        };
+        let config = Config {
+            applications: vec![
+                application("Telegram", true),
+                application("Kitty", false),
+                application("Firefox", true),
+            ],
+            ..Default::default()
+        };
+        let names = |only: &[&str]| {
+            let only = only.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+            config.autostart_applications(&only).map(|applications| {
+                applications
+                    .into_iter()
+                    .map(|application| application.name.as_str())
+                    .collect::<Vec<_>>()
             })
+        };
+
+        assert_eq!(names(&[]).unwrap(), ["Telegram", "Firefox"]);
+        assert_eq!(
+            names(&["Firefox", "Telegram"]).unwrap(),
+            ["Telegram", "Firefox"]
+        );
+        assert_eq!(
+            names(&["Kitty"]).unwrap_err().to_string(),
+            "Application 'Kitty' is not marked `autostart`, applications marked are: 'Telegram', 'Firefox'"
+        );
+    }
+
+    #[test]
+    fn from_stdin_names_the_source_in_diagnostics() {
+        let err = Config::from_stdin(" \n".as_bytes(), ConfigFormat::Toml).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No config given on stdin, pipe one in or pass a file with --config"
+        );
+
+        let err =
+            Config::from_stdin("[[application]\n".as_bytes(), ConfigFormat::Toml).unwrap_err();
+        let name = err
+            .source_code()
+            .and_then(|source| source.read_span(&(0..0).into(), 0, 0).ok())
+            .and_then(|span| span.name().map(str::to_string));
+        assert_eq!(err.to_string(), "Failed to parse stdin");
+        assert_eq!(name.as_deref(), Some(STDIN_NAME));
+    }
+
+    #[test]
+    fn from_str_as_ignores_the_extension() {
+        let text = "application:\n  - name: Firefox\n    spawn: [firefox]\n    match: [{app-id: firefox}]\n";
+
+        let config = Config::from_str_as("app-hotkey.conf", text, ConfigFormat::Yaml).unwrap();
+
+        assert_eq!(config.applications[0].name, "Firefox");
+        assert!(Config::from_str("app-hotkey.conf", text).is_err());
+    }
+
+    #[test]
+    fn from_str_inlines_referenced_match_sets() {
+        let text = "[[match-set]]\nname = \"noise\"\n[[match-set.rule]]\ntitle = \"^Picture-in-Picture$\"\n\n[[application]]\nname = \"Firefox\"\nspawn = [\"firefox\"]\nexclude-ref = [\"noise\"]\n[[application.match]]\napp-id = \"firefox\"\n";
+        let config = Config::from_str("config.toml", text).unwrap();
+
+        let application = config.find_application("Firefox").unwrap();
+        assert!(application.exclude_refs.is_empty());
+        assert_eq!(application.excludes.len(), 1);
+        assert!(
+            application.excludes[0]
+                .title
+                .as_ref()
+                .unwrap()
+                .is_match("Picture-in-Picture")
+        );
+        assert!(config.match_sets.is_empty());
+
+        let err = Config::from_str(
+            "config.toml",
+            &text.replace("= [\"noise\"]", "= [\"nois\"]"),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Application 'Firefox' references unknown match-set 'nois'"
+        );
+    }
+
+    #[test]
+    fn from_str_decodes_kdl_applications() {
+        let config = Config::from_str(
+            "config.kdl",
+            r#"
+            settings {
+                launch-timeout-ms 1000
+            }
+            application "Firefox" {
+                description "Personal profile"
+                spawn "firefox" "--new-window"
+                match app-id="^firefox$" index="last" is-floating=false
+                exclude title="Picture-in-Picture"
+            }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.settings.launch_timeout_ms, Some(1000));
+        let application = config.find_application("Firefox").unwrap();
+        assert_eq!(application.description.as_deref(), Some("Personal profile"));
+        assert_eq!(
+            application.spawn.as_deref(),
+            Some(["firefox".to_string(), "--new-window".to_string()].as_slice())
+        );
+        assert_eq!(application.matches[0].index, Some(WindowIndex::Last));
+        assert_eq!(application.matches[0].is_floating, Some(false));
+        assert!(
+            application.matches[0]
+                .app_id
+                .as_ref()
+                .unwrap()
+                .is_match("Firefox")
+        );
+        assert_eq!(application.excludes.len(), 1);
+    }
+
+    #[test]
+    fn from_str_decodes_kdl_multiline_spawn_sh() {
+        let config = Config::from_str(
+            "config.kdl",
+            r##"
+application "Scratch" {
+    spawn-sh r#"
+mkdir -p "$HOME/scratch"
+cd "$HOME/scratch" && exec kitty
+"#
+}
+"##,
+        )
+        .unwrap();
+
+        let application = config.find_application("Scratch").unwrap();
+        assert_eq!(
+            application.spawn_sh.as_deref(),
+            Some("\nmkdir -p \"$HOME/scratch\"\ncd \"$HOME/scratch\" && exec kitty\n")
+        );
+    }
+
+    #[test]
+    fn from_str_rejects_invalid_kdl() {
+        for text in [
+            r#"application "Firefox" { unknown-node }"#,
+            r#"application "Firefox" { match app-id="(" }"#,
+            r#"application "Firefox" { match index="second" }"#,
+            r#"application "Firefox" { on-focused "minimize" }"#,
+            r#"application "Firefox" {"#,
+        ] {
+            assert!(Config::from_str("config.kdl", text).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn from_str_selects_format_from_name() {
+        let cases = [
+            (
+                "config.toml",
+                "[[application]]\nname = \"Firefox\"\ndescription = \"Personal profile\"\nspawn = [\"firefox\"]\n[[application.match]]\napp-id = \"firefox\"\nindex = 1\nis-floating = true\n",
+            ),
+            (
+                "config.yaml",
+                "application:\n  - name: Firefox\n    description: Personal profile\n    spawn: [firefox]\n    match:\n      - app-id: firefox\n        index: 1\n        is-floating: true\n",
+            ),
+        ];
+
+        for (name, text) in cases {
+            let config = Config::from_str(name, text).unwrap();
+            let application = config.find_application("Firefox").unwrap();
+            assert_eq!(
+                application.matches[0].index,
+                Some(WindowIndex::Nth(1)),
+                "{name}"
+            );
+            assert_eq!(application.matches[0].is_floating, Some(true), "{name}");
+            assert_eq!(
+                application.description.as_deref(),
+                Some("Personal profile"),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn split_spawn_lines_honors_quotes_escapes_and_comments() {
+        let application = |line: &str| Application {
+            name: "Terminal".to_string(),
+            spawn_line: Some(line.to_string()),
+            ..Default::default()
+        };
+        let mut config = Config {
+            applications: vec![application(
+                r#"kitty --title "My \"Term\"" -e 'sh -c "echo $HOME"' a\ b "" # comment"#,
+            )],
+            ..Default::default()
+        };
+        config.split_spawn_lines().unwrap();
+
+        assert_eq!(
+            config.applications[0].spawn.as_deref().unwrap(),
+            [
+                "kitty",
+                "--title",
+                r#"My "Term""#,
+                "-e",
+                r#"sh -c "echo $HOME""#,
+                "a b",
+                ""
+            ]
+        );
+        let mut config = Config {
+            applications: vec![application("kitty 'unterminated")],
+            ..Default::default()
+        };
+        assert!(config.split_spawn_lines().is_err());
+    }
+
+    #[test]
+    fn from_str_splits_spawn_line_into_spawn() {
+        let text =
+            "[[application]]\nname = \"Terminal\"\nspawn-line = \"kitty --title 'My Term'\"\n";
+
+        let config = Config::from_str("config.toml", text).unwrap();
+
+        assert_eq!(
+            config.applications[0].spawn.as_deref(),
+            Some(["kitty", "--title", "My Term"].map(String::from).as_slice())
+        );
+        let err = Config::from_str("config.toml", &text.replace("kitty --title 'My Term'", " "))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "spawn-line of application 'Terminal' is empty"
+        );
+    }
+
+    #[test]
+    fn version_warning_compares_config_version() {
+        let warning = |text: &str| {
+            Config::from_str("config.toml", text)
+                .unwrap()
+                .version_warning()
+        };
+
+        assert_eq!(warning(""), None);
+        assert_eq!(warning(&format!("config-version = {CONFIG_VERSION}")), None);
+        assert!(
+            warning(&format!("config-version = {}", CONFIG_VERSION + 1))
+                .is_some_and(|warning| warning.contains("is newer than"))
+        );
+        assert!(
+            warning("config-version = 0").is_some_and(|warning| warning.contains("is older than"))
+        );
+    }
+
+    #[test]
+    fn from_str_rejects_invalid_snippets() {
+        let cases = [
+            (
+                "config.toml",
+                "[[application]]\nname = \"Firefox\"\nunknown = true\n",
+            ),
+            (
+                "config.toml",
+                "[[application]]\nname = \"Firefox\"\n[[application.match]]\nindex = \"second\"\n",
+            ),
+            (
+                "config.yaml",
+                "application:\n  - name: Firefox\n    match:\n      - app-id: \"(\"\n",
+            ),
+        ];
+
+        for (name, text) in cases {
+            assert!(Config::from_str(name, text).is_err(), "{name}: {text}");
+        }
+    }
+
+    #[test]
+    fn from_str_reports_every_invalid_pattern() {
+        let err = Config::from_str(
+            "config.yaml",
+            "settings:\n  hidden-workspace: \"[\"\napplication:\n  - name: Firefox\n    match:\n      - app-id: \"(\"\n        title: \")\"\n    group:\n      - exclude:\n          - title: \"[a-\"\n",
+        )
+        .unwrap_err();
+
+        let messages = err
+            .related()
+            .unwrap()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(messages.len(), 4);
+        assert!(messages[0].starts_with("settings: invalid `hidden-workspace` pattern"));
+        assert!(
+            messages[1].starts_with("match rule #1 of application 'Firefox': invalid `app-id`")
+        );
+        assert!(messages[2].starts_with("match rule #1 of application 'Firefox': invalid `title`"));
+        assert!(
+            messages[3].starts_with(
+                "exclude rule #1 of group #1 of application 'Firefox': invalid `title`"
+            )
+        );
     }
 }
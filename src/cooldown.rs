@@ -0,0 +1,141 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use directories::BaseDirs;
+use miette::{Context, IntoDiagnostic, Result, miette};
+
+const STATE_DIR_NAME: &str = "niri-app-hotkey/cooldown";
+
+/// When each application last acted, to ignore presses repeated within its `cooldown-ms`.
+pub enum Cooldowns {
+    /// One state file per application in this directory, so that the time outlives the
+    /// process. `None` stands for the default directory, which is looked up on first use.
+    Files(Option<PathBuf>),
+    /// Kept by the daemon, which handles every command itself.
+    Memory(Mutex<HashMap<String, SystemTime>>),
+}
+
+impl Cooldowns {
+    /// Keeps state files in `$XDG_RUNTIME_DIR/niri-app-hotkey/cooldown`.
+    pub fn in_runtime_dir() -> Self {
+        Self::Files(None)
+    }
+
+    pub fn in_memory() -> Self {
+        Self::Memory(Mutex::default())
+    }
+
+    /// Returns the time since the application `name` last acted, if that was less than
+    /// `cooldown` ago.
+    pub fn check(&self, name: &str, cooldown: Duration) -> Result<Option<Duration>> {
+        let last = match self {
+            Self::Files(dir) => fs::read_to_string(state_file(dir.as_deref(), name)?)
+                .ok()
+                .and_then(|text| text.trim().parse().ok())
+                .map(|millis| UNIX_EPOCH + Duration::from_millis(millis)),
+            Self::Memory(last_actions) => last_actions
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .get(name)
+                .copied(),
+        };
+
+        Ok(last
+            .and_then(|last| SystemTime::now().duration_since(last).ok())
+            .filter(|elapsed| *elapsed < cooldown))
+    }
+
+    /// Records that the application `name` acted now.
+    pub fn record(&self, name: &str) -> Result<()> {
+        let now = SystemTime::now();
+        match self {
+            Self::Files(dir) => {
+                let path = state_file(dir.as_deref(), name)?;
+                let millis = now
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+                path.parent()
+                    .map_or(Ok(()), fs::create_dir_all)
+                    .and_then(|()| fs::write(&path, millis.to_string()))
+                    .into_diagnostic()
+                    .context(format!("Failed to write cooldown state file at {path:?}"))?;
+            }
+            Self::Memory(last_actions) => {
+                last_actions
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner())
+                    .insert(name.to_string(), now);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the path of the state file of the application `name` in `dir`, or else in the
+/// default directory.
+fn state_file(dir: Option<&Path>, name: &str) -> Result<PathBuf> {
+    let dir = match dir {
+        Some(dir) => dir.to_path_buf(),
+        None => default_state_dir()?,
+    };
+
+    Ok(dir.join(state_file_name(name)))
+}
+
+fn default_state_dir() -> Result<PathBuf> {
+    BaseDirs::new()
+        .and_then(|dirs| dirs.runtime_dir().map(Path::to_path_buf))
+        .map(|runtime_dir| runtime_dir.join(STATE_DIR_NAME))
+        .ok_or_else(|| {
+            miette!("Could not determine where to keep cooldown state, $XDG_RUNTIME_DIR is not set")
+        })
+}
+
+/// Turns an application name into a file name, escaping characters other than ASCII
+/// letters, digits, `-` and `_` as `%XX`.
+fn state_file_name(name: &str) -> String {
+    name.bytes().fold(String::new(), |mut file_name, byte| {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            file_name.push(char::from(byte));
+        } else {
+            let _ = write!(file_name, "%{byte:02X}");
+        }
+        file_name
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    #[test]
+    fn check_reports_actions_within_cooldown() {
+        let dir = env::temp_dir().join(format!("niri-app-hotkey-cooldown-{}", process::id()));
+        let cooldowns = [Cooldowns::Files(Some(dir.clone())), Cooldowns::in_memory()];
+
+        for cooldowns in &cooldowns {
+            let cooldown = Duration::from_secs(60);
+            assert_eq!(cooldowns.check("Firefox", cooldown).unwrap(), None);
+            cooldowns.record("Firefox").unwrap();
+            assert!(cooldowns.check("Firefox", cooldown).unwrap().is_some());
+            assert_eq!(cooldowns.check("My Notes", cooldown).unwrap(), None);
+            cooldowns.record("My Notes").unwrap();
+            assert_eq!(cooldowns.check("Firefox", Duration::ZERO).unwrap(), None);
+        }
+
+        let files = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(files, 2);
+        assert_eq!(state_file_name("My Notes/2"), "My%20Notes%2F2");
+    }
+}
@@ -0,0 +1,371 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    thread,
+    time::Duration,
+};
+
+use directories::BaseDirs;
+use miette::{Context, IntoDiagnostic, Result, bail, miette};
+use serde::{Deserialize, Serialize};
+use signal_hook::{consts::SIGHUP, iterator::Signals};
+
+use crate::{
+    cache::{CachedSocket, WindowCache},
+    cli::Command,
+    config::{self, Config, ConfigFormat, Settings, WindowIndex},
+    cooldown::Cooldowns,
+    dispatch::run_application_command,
+    error::{self, AppError, format_report},
+    ipc::{LazySocket, NiriIpc},
+    log::{DaemonLog, LoggedSocket},
+    report::WindowReport,
+};
+
+const SOCKET_FILE_NAME: &str = "niri-app-hotkey.sock";
+
+/// How long a client may take to send its request or to read the response. Connections are
+/// handled one at a time, so a client that stalls must not hold up all others.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A command sent to the daemon. The protocol is line based: a client connects, writes a
+/// single request as a line of JSON and reads back a single [`DaemonResponse`] line, after
+/// which the connection is closed.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DaemonRequest {
+    pub command: Command,
+    /// Whether acting on multiple windows is approved up front, as the daemon cannot ask.
+    pub yes: bool,
+    /// Replaces the `index` of every match rule for this command.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_index: Option<WindowIndex>,
+    /// Settings given on the client's command line, e.g. `--first-match`. They take
+    /// precedence over those the daemon was started with.
+    #[serde(default)]
+    pub settings: Settings,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DaemonResponse {
+    /// The exit code the command would have had when run directly, 0 on success.
+    pub exit_code: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// What the command did to each window it acted on, for the client to print.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub windows: Vec<WindowReport>,
+}
+
+impl DaemonResponse {
+    fn from_result(result: Result<Vec<WindowReport>>) -> Self {
+        match result {
+            Ok(windows) => Self {
+                exit_code: 0,
+                error: None,
+                windows,
+            },
+            Err(report) => Self {
+                exit_code: error::exit_code(&report),
+                error: Some(format_report(&report)),
+                windows: vec![],
+            },
+        }
+    }
+}
+
+/// Returns the default path of the daemon socket, in `$XDG_RUNTIME_DIR`.
+pub fn default_socket_path() -> Result<PathBuf> {
+    let runtime_dir = BaseDirs::new()
+        .and_then(|dirs| dirs.runtime_dir().map(Path::to_path_buf))
+        .ok_or_else(|| {
+            miette!(
+                "Could not determine the daemon socket path, please provide one via --daemon-socket"
+            )
+        })?;
+
+    Ok(runtime_dir.join(SOCKET_FILE_NAME))
+}
+
+/// Listens for requests on `listen_path` and executes them until the process is killed.
+/// The config is parsed once, the connection to Niri is kept open between requests and the
+/// window list is kept up to date from Niri's event stream, so hotkeys do not pay for any
+/// of them on every press. Requests, the actions they send and their outcome are also
+/// written to `log`.
+pub fn serve(
+    listen_path: &Path,
+    config_path: &Path,
+    config_format: ConfigFormat,
+    niri_socket_path: Option<PathBuf>,
+    overrides: &Settings,
+    log: DaemonLog,
+) -> Result<()> {
+    // Stdin is used up by the first read, so reloading on SIGHUP could never succeed
+    if config::is_stdin(config_path) {
+        bail!("The daemon cannot read its config from stdin, pass a file with --config");
+    }
+    if UnixStream::connect(listen_path).is_ok() {
+        bail!("Another daemon is already listening on {listen_path:?}");
+    }
+    // A leftover socket file from a daemon that did not exit cleanly prevents binding
+    if listen_path.exists() {
+        fs::remove_file(listen_path)
+            .into_diagnostic()
+            .context(format!("Failed to remove stale socket at {listen_path:?}"))?;
+    }
+
+    let config = Arc::new(RwLock::new(Config::parse_as(config_path, config_format)?));
+    let log = Arc::new(log);
+    reload_on_sighup(
+        config_path.to_path_buf(),
+        config_format,
+        Arc::clone(&config),
+        Arc::clone(&log),
+    )?;
+    let listener = UnixListener::bind(listen_path)
+        .into_diagnostic()
+        .context(format!("Failed to listen on {listen_path:?}"))?;
+    let cache = WindowCache::subscribe(niri_socket_path.clone());
+    let mut socket = LazySocket::new(niri_socket_path);
+    let cooldowns = Cooldowns::in_memory();
+    eprintln!("Listening on {listen_path:?}");
+    log.write(format_args!("listening on {listen_path:?}"));
+
+    for stream in listener.incoming() {
+        let result = stream.into_diagnostic().and_then(|stream| {
+            let config = config.read().unwrap_or_else(|err| err.into_inner());
+            let mut socket = CachedSocket::new(&cache, &mut socket);
+            let mut socket = LoggedSocket::new(&mut socket, &log);
+            handle_connection(stream, &config, &mut socket, overrides, &cooldowns, &log)
+        });
+        if let Err(err) = result {
+            eprintln!("Warning: failed to handle request: {err}");
+            log.write(format_args!("failed to handle request: {err}"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Re-parses the config whenever the process receives SIGHUP and swaps it in. An invalid
+/// config is reported and the previous one is kept.
+fn reload_on_sighup(
+    config_path: PathBuf,
+    config_format: ConfigFormat,
+    config: Arc<RwLock<Config>>,
+    log: Arc<DaemonLog>,
+) -> Result<()> {
+    let mut signals = Signals::new([SIGHUP])
+        .into_diagnostic()
+        .context("Failed to register SIGHUP handler")?;
+
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            match Config::parse_as(&config_path, config_format) {
+                Ok(new_config) => {
+                    *config.write().unwrap_or_else(|err| err.into_inner()) = new_config;
+                    eprintln!("Reloaded config from {config_path:?}");
+                    log.write(format_args!("reloaded config from {config_path:?}"));
+                }
+                Err(err) => {
+                    eprintln!("Error: failed to reload config, keeping the previous one: {err:?}");
+                    log.write(format_args!(
+                        "failed to reload config, keeping the previous one: {}",
+                        format_report(&err)
+                    ));
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+fn handle_connection(
+    stream: UnixStream,
+    config: &Config,
+    socket: &mut impl NiriIpc,
+    overrides: &Settings,
+    cooldowns: &Cooldowns,
+    log: &DaemonLog,
+) -> Result<()> {
+    stream
+        .set_read_timeout(Some(CLIENT_TIMEOUT))
+        .and_then(|()| stream.set_write_timeout(Some(CLIENT_TIMEOUT)))
+        .into_diagnostic()?;
+    let mut line = String::new();
+    let read = BufReader::new(&stream).read_line(&mut line);
+    if matches!(read, Ok(0)) {
+        // The client only checked whether the daemon is running
+        return Ok(());
+    }
+    log.write(format_args!("request: {}", line.trim_end()));
+
+    let result = read
+        .into_diagnostic()
+        .context("Failed to read request")
+        .and_then(|_| {
+            serde_json::from_str::<DaemonRequest>(&line)
+                .into_diagnostic()
+                .context("Invalid request")
+        })
+        .and_then(|request| {
+            run_application_command(
+                request.command,
+                config,
+                socket,
+                &request.settings.or(overrides),
+                request.match_index,
+                cooldowns,
+                |_| Ok(request.yes),
+            )
+        });
+    match &result {
+        Ok(_) => log.write("result: ok"),
+        Err(err) => {
+            eprintln!("Error: {}", format_report(err));
+            log.write(format_args!(
+                "result: exit code {}: {}",
+                error::exit_code(err),
+                format_report(err)
+            ));
+        }
+    }
+
+    let response = serde_json::to_string(&DaemonResponse::from_result(result)).into_diagnostic()?;
+    writeln!(&stream, "{response}").into_diagnostic()?;
+
+    Ok(())
+}
+
+/// Sends a request to the daemon listening on `path` and turns a failure it reports into
+/// an error with the same exit code. Returns the windows the command acted on.
+pub fn send(path: &Path, request: &DaemonRequest) -> Result<Vec<WindowReport>> {
+    let stream = UnixStream::connect(path)
+        .into_diagnostic()
+        .context(format!(
+            "Failed to connect to the daemon at {path:?}, is `niri-app-hotkey daemon` running?"
+        ))?;
+
+    let request = serde_json::to_string(request).into_diagnostic()?;
+    writeln!(&stream, "{request}").into_diagnostic()?;
+
+    let mut line = String::new();
+    BufReader::new(&stream)
+        .read_line(&mut line)
+        .into_diagnostic()?;
+    let response = serde_json::from_str::<DaemonResponse>(&line)
+        .into_diagnostic()
+        .context("Invalid response from the daemon")?;
+
+    if response.exit_code != 0 {
+        bail!(AppError::Daemon {
+            message: response.error.unwrap_or_default(),
+            exit_code: response.exit_code,
+        });
+    }
+
+    Ok(response.windows)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{
+        cli::AdHocTarget,
+        config::{Application, MatchRule, Regex},
+        ipc::fake::{FakeIpc, window, workspace},
+    };
+
+    #[test]
+    fn stalled_client_gets_an_error_instead_of_blocking() {
+        let mut ipc = FakeIpc::new(vec![], vec![workspace(1, true, false)]);
+        let (client, server) = UnixStream::pair().unwrap();
+        write!(&client, "{{\"command\":").unwrap();
+
+        handle_connection(
+            server,
+            &Config::default(),
+            &mut ipc,
+            &Settings::default(),
+            &Cooldowns::in_memory(),
+            &DaemonLog::default(),
+        )
+        .unwrap();
+
+        let mut line = String::new();
+        BufReader::new(&client).read_line(&mut line).unwrap();
+        let response = serde_json::from_str::<DaemonResponse>(&line).unwrap();
+        assert_eq!(response.exit_code, 1);
+        assert!(
+            response.error.unwrap().contains("Failed to read request"),
+            "{line}"
+        );
+    }
+
+    #[test]
+    fn client_settings_take_precedence_over_the_daemon_ones() {
+        let config = Config {
+            applications: vec![Application {
+                name: "Firefox".to_string(),
+                matches: vec![MatchRule {
+                    app_id: Some(Regex::from_str("firefox").unwrap()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let mut ipc = FakeIpc::new(
+            vec![
+                window(1, "firefox", 2, false),
+                window(2, "firefox", 2, false),
+            ],
+            vec![workspace(1, true, false), workspace(2, false, false)],
+        );
+        let daemon_overrides = Settings {
+            first_match: Some(false),
+            ..Default::default()
+        };
+        let mut send = |first_match| {
+            let request = DaemonRequest {
+                command: Command::Show {
+                    application_names: vec!["Firefox".to_string()],
+                    target: AdHocTarget::default(),
+                    no_focus: true,
+                    output: None,
+                    ignore_missing: false,
+                },
+                yes: false,
+                match_index: None,
+                settings: Settings {
+                    first_match,
+                    ..Default::default()
+                },
+            };
+            let (client, server) = UnixStream::pair().unwrap();
+            writeln!(&client, "{}", serde_json::to_string(&request).unwrap()).unwrap();
+            handle_connection(
+                server,
+                &config,
+                &mut ipc,
+                &daemon_overrides,
+                &Cooldowns::in_memory(),
+                &DaemonLog::default(),
+            )
+            .unwrap();
+            let mut line = String::new();
+            BufReader::new(&client).read_line(&mut line).unwrap();
+            serde_json::from_str::<DaemonResponse>(&line).unwrap()
+        };
+
+        assert_ne!(send(None).exit_code, 0);
+        let response = send(Some(true));
+        assert_eq!(response.exit_code, 0);
+        assert_eq!(response.windows.len(), 1);
+    }
+}
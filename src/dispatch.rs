@@ -0,0 +1,888 @@
+use std::{borrow::Cow, thread, time::Duration};
+
+use miette::{Result, bail, miette};
+use niri_ipc::Window;
+
+use crate::{
+    action::{self, AfterHide},
+    cli::{AdHocTarget, Command},
+    config::{Application, Config, Settings, WindowIndex},
+    cooldown::Cooldowns,
+    error::AppError,
+    ipc::NiriIpc,
+    report::{ReportingSocket, WindowReport},
+};
+
+/// Runs `run` on each named application in order. A single application fails with its own
+/// error. With several, a failing one does not stop the rest, and the failures are summed
+/// up in one error at the end, while a run without failures is summed up on stderr. With
+/// `ignore_missing`, applications missing from the config are only warned about, and fail
+/// the command only if none of them is found.
+fn for_each_application(
+    config: &Config,
+    names: &[String],
+    ignore_missing: bool,
+    mut run: impl FnMut(&Application) -> Result<()>,
+) -> Result<()> {
+    if let [name] = names {
+        return run(config.find_application(name)?);
+    }
+
+    let mut missing = vec![];
+    let mut failures = vec![];
+    for name in names {
+        let application = match config.find_application(name) {
+            Ok(application) => application,
+            Err(err) if ignore_missing => {
+                eprintln!("Warning: skipping application '{name}': {err}");
+                missing.push(name.as_str());
+                continue;
+            }
+            Err(err) => {
+                failures.push(format!("{name}: {err}"));
+                continue;
+            }
+        };
+        if let Err(err) = run(application) {
+            failures.push(format!("{name}: {err}"));
+        }
+    }
+    if !missing.is_empty() && missing.len() == names.len() {
+        bail!(
+            "None of the {} applications were found: {}",
+            names.len(),
+            missing.join(", ")
+        );
+    }
+    if !failures.is_empty() {
+        bail!(
+            "{} of {} applications failed:\n{}",
+            failures.len(),
+            names.len(),
+            failures.join("\n")
+        );
+    }
+    // Stderr keeps the summary apart from the window IDs printed with --print-id
+    eprintln!("{}", success_summary(names.len(), missing.len()));
+
+    Ok(())
+}
+
+/// Sums up a run over `total` applications without failures, of which `skipped` were
+/// missing from the config.
+fn success_summary(total: usize, skipped: usize) -> String {
+    match skipped {
+        0 => format!("All {total} applications succeeded"),
+        skipped => format!(
+            "{} of {total} applications succeeded, {skipped} skipped as missing",
+            total - skipped
+        ),
+    }
+}
+
+/// Picks where focus goes after hiding from the mutually exclusive command line flags.
+fn after_hide(
+    focus_previous: bool,
+    focus_workspace: Option<&str>,
+    previous_workspace: bool,
+) -> AfterHide<'_> {
+    match focus_workspace {
+        Some(reference) => AfterHide::FocusWorkspace(reference),
+        None if focus_previous => AfterHide::FocusPreviousWindow,
+        None if previous_workspace => AfterHide::PreviousWorkspace,
+        None => AfterHide::Default,
+    }
+}
+
+/// Returns the application built from the ad-hoc `target` with `match_index` applied, or
+/// else the one named `name`.
+fn target_application<'a>(
+    config: &'a Config,
+    name: Option<&str>,
+    target: &AdHocTarget,
+    match_index: Option<WindowIndex>,
+) -> Result<Cow<'a, Application>> {
+    if let Some(application) = target.application(match_index)? {
+        return Ok(Cow::Owned(application));
+    }
+    let name = name.ok_or_else(|| miette!("No application name given"))?;
+
+    Ok(Cow::Borrowed(config.find_application(name)?))
+}
+
+/// Returns the names of the applications of the command that have a `cooldown-ms`, or
+/// `None` if one of them acted within it, so that the command is to be ignored. The names
+/// are those from the config, so that a prefix of a name shares its cooldown.
+fn check_cooldowns(
+    command: &Command,
+    config: &Config,
+    overrides: &Settings,
+    cooldowns: &Cooldowns,
+) -> Result<Option<Vec<String>>> {
+    let mut names = vec![];
+    for name in command.application_names() {
+        // Unknown applications are reported when running the command
+        let Ok(application) = config.find_application(name) else {
+            continue;
+        };
+        let Some(cooldown) = overrides.or(&config.settings_for(application)).cooldown() else {
+            continue;
+        };
+        let name = &application.name;
+        if let Some(elapsed) = cooldowns.check(name, cooldown)? {
+            eprintln!(
+                "Ignoring command for '{name}', which acted {}ms ago, within its cooldown of {}ms",
+                elapsed.as_millis(),
+                cooldown.as_millis()
+            );
+            return Ok(None);
+        }
+        names.push(name.clone());
+    }
+
+    Ok(Some(names))
+}
+
+/// Runs a command that acts on an application, so that both the CLI and the daemon can
+/// execute it. `overrides` take precedence over the settings from the config file,
+/// `match_index` replaces the `index` of every match rule, `cooldowns` keeps track of when
+/// applications last acted, and `confirm` approves acting on multiple windows. Returns a
+/// report for each window the command acted on.
+pub fn run_application_command(
+    command: Command,
+    config: &Config,
+    socket: &mut impl NiriIpc,
+    overrides: &Settings,
+    match_index: Option<WindowIndex>,
+    cooldowns: &Cooldowns,
+    confirm: impl FnOnce(&[&Window]) -> Result<bool>,
+) -> Result<Vec<WindowReport>> {
+    // Failing to keep track of cooldowns should not make the hotkey stop working
+    let cooled_down = match check_cooldowns(&command, config, overrides, cooldowns) {
+        Ok(Some(names)) => names,
+        Ok(None) => return Ok(vec![]),
+        Err(err) => {
+            eprintln!("Warning: ignoring cooldown: {err}");
+            vec![]
+        }
+    };
+
+    let mut socket = ReportingSocket::new(socket);
+    match match_index {
+        Some(match_index) => run(
+            command,
+            &config.with_match_index(match_index),
+            &mut socket,
+            overrides,
+            Some(match_index),
+            confirm,
+        )
+        // A plain mismatch would hide that the index is beyond the matched windows
+        .map_err(|err| match err.downcast_ref() {
+            Some(AppError::NoMatch) => err.wrap_err(format!(
+                "No window at --match-index {match_index}, the rules match fewer windows"
+            )),
+            _ => err,
+        })?,
+        None => run(command, config, &mut socket, overrides, None, confirm)?,
+    }
+    // Only a command that did its job starts a cooldown, so that a failed press can be retried
+    for name in cooled_down {
+        if let Err(err) = cooldowns.record(&name) {
+            eprintln!("Warning: ignoring cooldown: {err}");
+        }
+    }
+
+    Ok(socket.reports())
+}
+
+/// Runs the command against `config`, which already has `match_index` applied. Ad-hoc
+/// targets are not part of it, so the index is applied to them here.
+fn run(
+    command: Command,
+    config: &Config,
+    socket: &mut impl NiriIpc,
+    overrides: &Settings,
+    match_index: Option<WindowIndex>,
+    confirm: impl FnOnce(&[&Window]) -> Result<bool>,
+) -> Result<()> {
+    let settings_for = |application: &Application| overrides.or(&config.settings_for(application));
+
+    match command {
+        Command::Launch {
+            application_names,
+            focus,
+            timeout,
+            if_absent,
+            print_command,
+            ignore_missing,
+            command: spawn_override,
+        } => {
+            for_each_application(config, &application_names, ignore_missing, |application| {
+                if if_absent && action::has_matched_windows(socket, application)? {
+                    return Ok(());
+                }
+                let overridden;
+                let application = match &spawn_override {
+                    Some(spawn) => {
+                        overridden = Application {
+                            spawn: Some(spawn.clone()),
+                            spawn_sh: None,
+                            ..application.clone()
+                        };
+                        &overridden
+                    }
+                    None => application,
+                };
+                let settings = settings_for(application);
+                // Look up the matched window only when the spawn command references it
+                let window = if action::uses_placeholders(application) {
+                    action::find_matched_window(socket, application, &settings)?
+                } else {
+                    None
+                };
+                if print_command {
+                    println!(
+                        "{}",
+                        action::format_command_line(application, &settings, window.as_ref())?
+                    );
+                    return Ok(());
+                }
+                if focus {
+                    let timeout = timeout
+                        .map(Duration::from_millis)
+                        .unwrap_or_else(|| settings.launch_timeout());
+                    action::launch_and_focus(
+                        socket,
+                        application,
+                        &settings,
+                        timeout,
+                        window.as_ref(),
+                    )
+                } else {
+                    action::launch(application, &settings, window.as_ref())
+                }
+            })?;
+        }
+        Command::Start { only } => {
+            let names = config
+                .autostart_applications(&only)?
+                .into_iter()
+                .map(|application| application.name.clone())
+                .collect::<Vec<_>>();
+            if names.is_empty() {
+                bail!("No applications are marked `autostart` in the configuration");
+            }
+            let mut launched_any = false;
+            for_each_application(config, &names, false, |application| {
+                let settings = settings_for(application);
+                // Stagger the launches so that they do not all compete for the system at once
+                if launched_any {
+                    thread::sleep(settings.autostart_delay());
+                }
+                launched_any = true;
+                let window = if action::uses_placeholders(application) {
+                    action::find_matched_window(socket, application, &settings)?
+                } else {
+                    None
+                };
+                action::launch(application, &settings, window.as_ref())
+            })?;
+        }
+        Command::Show {
+            application_names,
+            target,
+            no_focus,
+            output,
+            ignore_missing,
+        } => {
+            let mut show = |application: &Application| {
+                action::show(
+                    socket,
+                    application,
+                    &settings_for(application),
+                    !no_focus,
+                    output.as_deref(),
+                )
+            };
+            match target.application(match_index)? {
+                Some(application) => show(&application)?,
+                None => for_each_application(config, &application_names, ignore_missing, show)?,
+            }
+        }
+        Command::Hide {
+            application_name,
+            target,
+            focus_previous,
+            focus_workspace,
+            toggle_workspace,
+        } => {
+            let application =
+                target_application(config, application_name.as_deref(), &target, match_index)?;
+            action::hide(
+                socket,
+                &application,
+                &settings_for(&application),
+                after_hide(focus_previous, focus_workspace.as_deref(), toggle_workspace),
+            )?;
+        }
+        Command::Focus { application_name } => {
+            let application = config.find_application(&application_name)?;
+            action::focus(socket, application, &settings_for(application))?;
+        }
+        Command::Activate {
+            application_name,
+            anywhere,
+        } => {
+            let application = config.find_application(&application_name)?;
+            action::activate(socket, application, &settings_for(application), anywhere)?;
+        }
+        Command::Close {
+            application_name,
+            all,
+        } => {
+            let application = config.find_application(&application_name)?;
+            action::close(
+                socket,
+                application,
+                &settings_for(application),
+                all,
+                confirm,
+            )?;
+        }
+        Command::Kill {
+            application_name,
+            force,
+        } => {
+            let application = config.find_application(&application_name)?;
+            action::kill(socket, application, &settings_for(application), force)?;
+        }
+        Command::Toggle {
+            application_name,
+            target,
+            focus_previous,
+            fullscreen,
+            workspace,
+            focus_workspace,
+        } => {
+            let application =
+                target_application(config, application_name.as_deref(), &target, match_index)?;
+            if workspace {
+                action::toggle_workspace(socket, &application, &settings_for(&application))?;
+            } else {
+                action::toggle(
+                    socket,
+                    &application,
+                    &settings_for(&application),
+                    fullscreen,
+                    after_hide(focus_previous, focus_workspace.as_deref(), false),
+                )?;
+            }
+        }
+        Command::Raise {
+            application_name,
+            no_launch,
+            no_wait,
+            timeout,
+            no_switch,
+        } => {
+            let application = config.find_application(&application_name)?;
+            let settings = settings_for(application);
+            let timeout = (!no_wait).then(|| {
+                timeout
+                    .map(Duration::from_millis)
+                    .unwrap_or_else(|| settings.launch_timeout())
+            });
+            action::smart_raise(
+                socket,
+                application,
+                &settings,
+                !no_launch,
+                timeout,
+                !no_switch,
+            )?;
+        }
+        _ => bail!("Only commands that act on an application can be run this way"),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, time::Instant};
+
+    use niri_ipc::{Action, Request, WorkspaceReferenceArg};
+
+    use super::*;
+    use crate::{
+        config::{MatchRule, Regex},
+        ipc::fake::{FakeIpc, window, workspace},
+    };
+
+    fn application(app_id: &str) -> Application {
+        Application {
+            name: app_id.to_string(),
+            matches: vec![MatchRule {
+                app_id: Some(Regex::from_str(app_id).unwrap()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn show_continues_after_a_failing_application() {
+        let config = Config {
+            applications: vec![application("firefox"), application("kitty")],
+            ..Default::default()
+        };
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 2, false), window(2, "kitty", 2, false)],
+            vec![workspace(1, true, false), workspace(2, false, false)],
+        );
+        let command = Command::Show {
+            application_names: ["firefox", "missing", "kitty"].map(String::from).to_vec(),
+            target: AdHocTarget::default(),
+            no_focus: true,
+            output: None,
+            ignore_missing: false,
+        };
+
+        let err = run_application_command(
+            command,
+            &config,
+            &mut ipc,
+            &Settings::default(),
+            None,
+            &Cooldowns::in_memory(),
+            |_| Ok(false),
+        )
+        .unwrap_err();
+
+        assert!(
+            err.to_string()
+                .starts_with("1 of 3 applications failed:\nmissing: ")
+        );
+        let moved_windows = ipc
+            .actions()
+            .into_iter()
+            .filter_map(|request| match request {
+                Request::Action(Action::MoveWindowToWorkspace { window_id, .. }) => *window_id,
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(moved_windows, [1, 2]);
+    }
+
+    #[test]
+    fn success_summary_counts_skipped_applications() {
+        assert_eq!(success_summary(3, 0), "All 3 applications succeeded");
+        assert_eq!(
+            success_summary(3, 1),
+            "2 of 3 applications succeeded, 1 skipped as missing"
+        );
+    }
+
+    #[test]
+    fn show_ignores_missing_applications_unless_all_are_missing() {
+        let config = Config {
+            applications: vec![application("firefox")],
+            ..Default::default()
+        };
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 2, false)],
+            vec![workspace(1, true, false), workspace(2, false, false)],
+        );
+        let mut show = |names: [&str; 2]| {
+            let command = Command::Show {
+                application_names: names.map(String::from).to_vec(),
+                target: AdHocTarget::default(),
+                no_focus: true,
+                output: None,
+                ignore_missing: true,
+            };
+            run_application_command(
+                command,
+                &config,
+                &mut ipc,
+                &Settings::default(),
+                None,
+                &Cooldowns::in_memory(),
+                |_| Ok(false),
+            )
+        };
+
+        let reports = show(["missing", "firefox"]).unwrap();
+        assert_eq!(
+            reports.iter().map(|report| report.id).collect::<Vec<_>>(),
+            [1]
+        );
+        let err = show(["missing", "gone"]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "None of the 2 applications were found: missing, gone"
+        );
+    }
+
+    #[test]
+    fn start_waits_between_launches_but_not_before_the_first() {
+        let application = |name: &str, delay_ms| Application {
+            name: name.to_string(),
+            spawn: Some(vec!["true".to_string()]),
+            autostart: true,
+            settings: Settings {
+                autostart_delay_ms: Some(delay_ms),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let config = Config {
+            applications: vec![application("first", 5_000), application("second", 50)],
+            ..Default::default()
+        };
+        let mut ipc = FakeIpc::new(vec![], vec![workspace(1, true, false)]);
+
+        let started = Instant::now();
+        run_application_command(
+            Command::Start { only: vec![] },
+            &config,
+            &mut ipc,
+            &Settings::default(),
+            None,
+            &Cooldowns::in_memory(),
+            |_| Ok(false),
+        )
+        .unwrap();
+        let elapsed = started.elapsed();
+
+        assert!(elapsed >= Duration::from_millis(50), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(5), "{elapsed:?}");
+    }
+
+    #[test]
+    fn show_targets_ad_hoc_rule_without_configured_application() {
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 2, false), window(2, "kitty", 2, false)],
+            vec![workspace(1, true, false), workspace(2, false, false)],
+        );
+        let command = Command::Show {
+            application_names: vec![],
+            target: AdHocTarget {
+                app_id: Some("^kitty$".to_string()),
+                title: None,
+            },
+            no_focus: true,
+            output: None,
+            ignore_missing: false,
+        };
+
+        let reports = run_application_command(
+            command,
+            &Config::default(),
+            &mut ipc,
+            &Settings::default(),
+            None,
+            &Cooldowns::in_memory(),
+            |_| Ok(false),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [Request::Action(Action::MoveWindowToWorkspace {
+                window_id: Some(2),
+                ..
+            })]
+        ));
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].id, 2);
+        assert_eq!(
+            reports[0].message,
+            "Moved window 'kitty window 2' (id 2) to workspace 1"
+        );
+    }
+
+    #[test]
+    fn focus_switches_to_the_window_workspace() {
+        let config = Config {
+            applications: vec![application("firefox"), application("kitty")],
+            ..Default::default()
+        };
+        let workspaces = vec![
+            workspace(1, true, false),
+            workspace(2, false, false),
+            workspace(3, false, true),
+        ];
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 2, false), window(2, "kitty", 3, false)],
+            workspaces,
+        );
+        let focus = |name: &str| Command::Focus {
+            application_name: name.to_string(),
+        };
+
+        run_application_command(
+            focus("firefox"),
+            &config,
+            &mut ipc,
+            &Settings::default(),
+            None,
+            &Cooldowns::in_memory(),
+            |_| Ok(false),
+        )
+        .unwrap();
+        let err = run_application_command(
+            focus("kitty"),
+            &config,
+            &mut ipc,
+            &Settings::default(),
+            None,
+            &Cooldowns::in_memory(),
+            |_| Ok(false),
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [
+                Request::Action(Action::FocusWorkspace {
+                    reference: WorkspaceReferenceArg::Id(2),
+                }),
+                Request::Action(Action::FocusWindow { id: 1 }),
+            ]
+        ));
+        assert!(matches!(err.downcast_ref(), Some(AppError::AlreadyHidden)));
+    }
+
+    #[test]
+    fn cooldown_ignores_repeated_commands() {
+        let config = Config {
+            settings: Settings {
+                cooldown_ms: Some(60_000),
+                ..Default::default()
+            },
+            applications: vec![application("firefox")],
+            ..Default::default()
+        };
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 2, false)],
+            vec![workspace(1, true, false), workspace(2, false, false)],
+        );
+        let cooldowns = Cooldowns::in_memory();
+
+        for _ in 0..2 {
+            let command = Command::Focus {
+                application_name: "firefox".to_string(),
+            };
+            run_application_command(
+                command,
+                &config,
+                &mut ipc,
+                &Settings::default(),
+                None,
+                &cooldowns,
+                |_| Ok(false),
+            )
+            .unwrap();
+        }
+
+        assert_eq!(ipc.actions().len(), 2);
+        assert!(matches!(
+            ipc.actions().last(),
+            Some(Request::Action(Action::FocusWindow { id: 1 }))
+        ));
+    }
+
+    #[test]
+    fn cooldown_is_shared_by_prefixes_of_the_name() {
+        let config = Config {
+            settings: Settings {
+                cooldown_ms: Some(60_000),
+                ..Default::default()
+            },
+            applications: vec![application("firefox")],
+            ..Default::default()
+        };
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 2, false)],
+            vec![workspace(1, true, false), workspace(2, false, false)],
+        );
+        let cooldowns = Cooldowns::in_memory();
+        let mut focus = |name: &str| {
+            let command = Command::Focus {
+                application_name: name.to_string(),
+            };
+            run_application_command(
+                command,
+                &config,
+                &mut ipc,
+                &Settings::default(),
+                None,
+                &cooldowns,
+                |_| Ok(false),
+            )
+            .unwrap()
+        };
+
+        assert_eq!(focus("firefox").len(), 1);
+        assert!(focus("fire").is_empty());
+    }
+
+    #[test]
+    fn cooldown_starts_only_when_the_command_succeeds() {
+        let config = Config {
+            settings: Settings {
+                cooldown_ms: Some(60_000),
+                ..Default::default()
+            },
+            applications: vec![application("firefox")],
+            ..Default::default()
+        };
+        let mut ipc = FakeIpc::new(vec![], vec![workspace(1, true, false)]);
+        let cooldowns = Cooldowns::in_memory();
+        let command = Command::Focus {
+            application_name: "firefox".to_string(),
+        };
+
+        run_application_command(
+            command,
+            &config,
+            &mut ipc,
+            &Settings::default(),
+            None,
+            &cooldowns,
+            |_| Ok(false),
+        )
+        .unwrap_err();
+
+        let cooldown = Duration::from_secs(60);
+        assert_eq!(cooldowns.check("firefox", cooldown).unwrap(), None);
+    }
+
+    #[test]
+    fn cooldown_of_one_application_skips_the_others_without_recording_them() {
+        let config = Config {
+            settings: Settings {
+                cooldown_ms: Some(60_000),
+                ..Default::default()
+            },
+            applications: vec![application("firefox"), application("kitty")],
+            ..Default::default()
+        };
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 2, false), window(2, "kitty", 2, false)],
+            vec![workspace(1, true, false), workspace(2, false, false)],
+        );
+        let cooldowns = Cooldowns::in_memory();
+        cooldowns.record("kitty").unwrap();
+        let command = Command::Show {
+            application_names: ["firefox", "kitty"].map(String::from).to_vec(),
+            target: AdHocTarget::default(),
+            no_focus: true,
+            output: None,
+            ignore_missing: false,
+        };
+
+        let reports = run_application_command(
+            command,
+            &config,
+            &mut ipc,
+            &Settings::default(),
+            None,
+            &cooldowns,
+            |_| Ok(false),
+        )
+        .unwrap();
+
+        assert!(reports.is_empty());
+        assert!(ipc.actions().is_empty());
+        let cooldown = Duration::from_secs(60);
+        assert_eq!(cooldowns.check("firefox", cooldown).unwrap(), None);
+    }
+
+    #[test]
+    fn match_index_overrides_the_index_of_match_rules() {
+        let config = Config {
+            applications: vec![application("kitty")],
+            ..Default::default()
+        };
+        let mut ipc = FakeIpc::new(
+            vec![
+                window(1, "kitty", 1, true),
+                window(2, "kitty", 1, false),
+                window(3, "kitty", 1, false),
+            ],
+            vec![workspace(1, true, false)],
+        );
+        let activate = || Command::Activate {
+            application_name: "kitty".to_string(),
+            anywhere: false,
+        };
+
+        run_application_command(
+            activate(),
+            &config,
+            &mut ipc,
+            &Settings::default(),
+            Some(WindowIndex::Nth(1)),
+            &Cooldowns::in_memory(),
+            |_| Ok(false),
+        )
+        .unwrap();
+        let err = run_application_command(
+            activate(),
+            &config,
+            &mut ipc,
+            &Settings::default(),
+            Some(WindowIndex::Nth(3)),
+            &Cooldowns::in_memory(),
+            |_| Ok(false),
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            ipc.actions().as_slice(),
+            [Request::Action(Action::FocusWindow { id: 2 })]
+        ));
+        assert_eq!(
+            err.to_string(),
+            "No window at --match-index 3, the rules match fewer windows"
+        );
+        assert!(matches!(err.downcast_ref(), Some(AppError::NoMatch)));
+    }
+
+    #[test]
+    fn match_index_applies_to_ad_hoc_targets() {
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "kitty", 2, false), window(2, "kitty", 2, false)],
+            vec![workspace(1, true, false), workspace(2, false, false)],
+        );
+        let command = Command::Show {
+            application_names: vec![],
+            target: AdHocTarget {
+                app_id: Some("^kitty$".to_string()),
+                title: None,
+            },
+            no_focus: true,
+            output: None,
+            ignore_missing: false,
+        };
+
+        let reports = run_application_command(
+            command,
+            &Config::default(),
+            &mut ipc,
+            &Settings::default(),
+            Some(WindowIndex::Nth(1)),
+            &Cooldowns::in_memory(),
+            |_| Ok(false),
+        )
+        .unwrap();
+
+        assert_eq!(
+            reports.iter().map(|report| report.id).collect::<Vec<_>>(),
+            [2]
+        );
+    }
+}
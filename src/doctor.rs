@@ -0,0 +1,96 @@
+use std::{env, path::Path};
+
+use miette::{Result, bail, miette};
+use niri_ipc::{Request, Response, socket::SOCKET_PATH_ENV};
+
+use crate::{
+    config::{self, Config, ConfigFormat},
+    ipc::{self, NiriIpc},
+};
+
+/// Prints a pass/fail line for a check and returns whether it passed.
+fn report(name: &str, result: Result<String>) -> bool {
+    match result {
+        Ok(detail) => {
+            println!("[PASS] {name}: {detail}");
+            true
+        }
+        Err(err) => {
+            println!("[FAIL] {name}: {err}");
+            false
+        }
+    }
+}
+
+/// Runs a series of environment checks, printing the outcome of each one. Checks that
+/// depend on an earlier one are skipped when it fails. Fails if any check failed.
+pub fn doctor(
+    config_path: &Path,
+    config_format: ConfigFormat,
+    socket_path: Option<&Path>,
+) -> Result<()> {
+    let mut failed = 0;
+    let mut check = |name: &str, result: Result<String>| {
+        let passed = report(name, result);
+        if !passed {
+            failed += 1;
+        }
+        passed
+    };
+
+    let config_exists = check(
+        "Config file exists",
+        if config::is_stdin(config_path) {
+            Ok("reading from stdin".to_string())
+        } else if config_path.exists() {
+            Ok(format!("{config_path:?}"))
+        } else {
+            Err(miette!(
+                "{config_path:?} not found, run `niri-app-hotkey init` to create it"
+            ))
+        },
+    );
+    if config_exists {
+        check(
+            "Config file parses",
+            Config::parse_as(config_path, config_format)
+                .map(|config| format!("{} application(s) configured", config.applications.len())),
+        );
+    }
+
+    let socket_known = check(
+        "Niri socket path is known",
+        match socket_path {
+            Some(path) => Ok(format!("{path:?} (from --socket)")),
+            None => env::var(SOCKET_PATH_ENV)
+                .map(|path| format!("{path} (from ${SOCKET_PATH_ENV})"))
+                .map_err(|_| miette!("${SOCKET_PATH_ENV} is not set, is Niri running?")),
+        },
+    );
+    if socket_known {
+        match ipc::connect(socket_path) {
+            Ok(mut socket) => {
+                check("Niri socket connects", Ok("connected".to_string()));
+                check(
+                    "Niri responds to requests",
+                    match NiriIpc::send(&mut socket, Request::Windows) {
+                        Ok(Response::Windows(windows)) => {
+                            Ok(format!("{} window(s) open", windows.len()))
+                        }
+                        Ok(_) => Err(miette!("unexpected response to window query")),
+                        Err(err) => Err(err),
+                    },
+                );
+            }
+            Err(err) => {
+                check("Niri socket connects", Err(err));
+            }
+        }
+    }
+
+    if failed > 0 {
+        bail!("{failed} check(s) failed");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use miette::{Context, IntoDiagnostic, Result, bail, miette};
+
+use crate::{
+    action::expand_home,
+    config::{self, Config, ConfigFormat},
+};
+
+const DEFAULT_EDITOR: &str = "vi";
+
+fn get_editor() -> String {
+    ["EDITOR", "VISUAL"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_string())
+}
+
+/// Opens the config file in the user's editor and waits for it to exit, creating the file
+/// from the starter template in `format` first if it does not exist.
+pub fn edit(path: &Path, format: ConfigFormat) -> Result<()> {
+    if config::is_stdin(path) {
+        bail!("Cannot edit a config read from stdin, pass a file with --config");
+    }
+    if !path.exists() {
+        Config::init(path, format, false)?;
+    }
+
+    // The editor may include arguments, e.g. `code --wait`
+    let editor = get_editor();
+    let mut parts = editor.split_whitespace();
+    let program = parts
+        .next()
+        .ok_or_else(|| miette!("Editor command is empty"))?;
+    let command = expand_home(PathBuf::from(program))?;
+
+    let status = Command::new(command)
+        .args(parts)
+        .arg(path)
+        .status()
+        .into_diagnostic()
+        .context(format!("Failed to run editor '{editor}'"))?;
+    if !status.success() {
+        bail!("Editor '{editor}' exited with {status}");
+    }
+
+    Ok(())
+}
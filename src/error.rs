@@ -0,0 +1,80 @@
+use miette::{Diagnostic, Report};
+use niri_ipc::Window;
+use thiserror::Error;
+
+/// Errors that callers may want to handle programmatically. Each kind maps to its own exit
+/// code, while other errors exit with 1.
+#[derive(Debug, Error, Diagnostic)]
+pub enum AppError {
+    #[error("No window matched the given rules.")]
+    NoMatch,
+
+    #[error(
+        "Multiple windows matched the given rules. Refine your match/exclude rules to target a single window, or set the `multi` setting. Matched windows: {0:#?}"
+    )]
+    MultipleMatches(Vec<Window>),
+
+    #[error("The matched window is not focused, cannot {0} it.")]
+    NotFocused(&'static str),
+
+    #[error("The matched window is not in the focused workspace, cannot {0} it.")]
+    NotOnFocusedWorkspace(&'static str),
+
+    #[error("The matched window is already in the hidden workspace.")]
+    AlreadyHidden,
+
+    #[error("The focused workspace is the hidden workspace, cannot {0} a window there.")]
+    HiddenWorkspaceFocused(&'static str),
+
+    #[error("No hidden workspace found")]
+    #[diagnostic(help(
+        "Configure a hidden workspace in Niri or set `hidden-workspace` in the settings."
+    ))]
+    NoHiddenWorkspace,
+
+    #[error("{0}")]
+    IpcFailed(String),
+
+    #[error("{0}")]
+    ConfigInvalid(String),
+
+    #[error("{0}")]
+    Cancelled(&'static str),
+
+    /// An error reported by the daemon, with the exit code it would have caused locally.
+    #[error("{message}")]
+    Daemon { message: String, exit_code: u8 },
+}
+
+impl AppError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::NoMatch => 3,
+            Self::MultipleMatches(_) => 4,
+            Self::NotFocused(_) | Self::NotOnFocusedWorkspace(_) => 5,
+            Self::AlreadyHidden | Self::HiddenWorkspaceFocused(_) => 6,
+            Self::NoHiddenWorkspace => 7,
+            Self::IpcFailed(_) => 8,
+            Self::ConfigInvalid(_) => 9,
+            Self::Cancelled(_) => 10,
+            Self::Daemon { exit_code, .. } => *exit_code,
+        }
+    }
+}
+
+/// Formats an error and its causes on a single line.
+pub fn format_report(report: &Report) -> String {
+    report
+        .chain()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(": ")
+}
+
+/// Returns the exit code for an error, falling back to 1 for errors that are not an
+/// [`AppError`].
+pub fn exit_code(report: &Report) -> u8 {
+    report
+        .downcast_ref::<AppError>()
+        .map_or(1, AppError::exit_code)
+}
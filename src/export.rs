@@ -0,0 +1,329 @@
+use std::fmt::{Display, Write};
+
+use miette::{IntoDiagnostic, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{
+    Application, Config, MatchGroup, MatchRule, MatchSet, MultiPolicy, OnFocused, RequireMode,
+    Settings, SpawnScope, WindowIndex,
+};
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, clap::ValueEnum)]
+pub enum ExportFormat {
+    #[default]
+    Kdl,
+    Json,
+}
+
+/// Prints the config as it is applied, see [`Config::resolved`].
+pub fn export(config: &Config, format: ExportFormat) -> Result<()> {
+    let config = config.resolved();
+    match format {
+        ExportFormat::Kdl => print!("{}", to_kdl(&config)),
+        ExportFormat::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&config).into_diagnostic()?
+        ),
+    }
+
+    Ok(())
+}
+
+/// Quotes a string as a KDL string literal.
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{{{:x}}}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+/// Serializes the config as KDL with one node per line, in the order the fields are
+/// declared, so that the output of two configs can be compared with `diff`.
+pub fn to_kdl(config: &Config) -> String {
+    let mut kdl = String::new();
+    if let Some(version) = config.config_version {
+        write_node(&mut kdl, 0, "config-version", version);
+    }
+    write_settings(&mut kdl, &config.settings, 0);
+    for match_set in &config.match_sets {
+        write_match_set(&mut kdl, match_set);
+    }
+    for application in &config.applications {
+        write_application(&mut kdl, application);
+    }
+
+    kdl
+}
+
+fn write_node(kdl: &mut String, depth: usize, name: &str, value: impl Display) {
+    let _ = writeln!(kdl, "{}{name} {value}", "    ".repeat(depth));
+}
+
+fn write_settings(kdl: &mut String, settings: &Settings, depth: usize) {
+    let mut nodes = String::new();
+    let inner = depth + 1;
+    if let Some(shell) = &settings.shell {
+        write_node(&mut nodes, inner, "shell", quote(shell));
+    }
+    if let Some(timeout) = settings.launch_timeout_ms {
+        write_node(&mut nodes, inner, "launch-timeout-ms", timeout);
+    }
+    if let Some(hidden_workspace) = &settings.hidden_workspace {
+        write_node(
+            &mut nodes,
+            inner,
+            "hidden-workspace",
+            quote(hidden_workspace.as_str()),
+        );
+    }
+    if let Some(timeout) = settings.rematch_timeout_ms {
+        write_node(&mut nodes, inner, "rematch-timeout-ms", timeout);
+    }
+    if let Some(inherit_io) = settings.inherit_io {
+        write_node(&mut nodes, inner, "inherit-io", inherit_io);
+    }
+    if let Some(preserve_floating) = settings.preserve_floating {
+        write_node(&mut nodes, inner, "preserve-floating", preserve_floating);
+    }
+    if let Some(first_match) = settings.first_match {
+        write_node(&mut nodes, inner, "first-match", first_match);
+    }
+    if let Some(verify_focus) = settings.verify_focus {
+        write_node(&mut nodes, inner, "verify-focus", verify_focus);
+    }
+    if let Some(verify) = settings.verify {
+        write_node(&mut nodes, inner, "verify", verify);
+    }
+    if let Some(cooldown) = settings.cooldown_ms {
+        write_node(&mut nodes, inner, "cooldown-ms", cooldown);
+    }
+    if let Some(delay) = settings.autostart_delay_ms {
+        write_node(&mut nodes, inner, "autostart-delay-ms", delay);
+    }
+    if let Some(multi) = settings.multi {
+        let multi = match multi {
+            MultiPolicy::Error => "error",
+            MultiPolicy::First => "first",
+            MultiPolicy::All => "all",
+        };
+        write_node(&mut nodes, inner, "multi", quote(multi));
+    }
+    // Leave out the block when nothing in it is set
+    if nodes.is_empty() {
+        return;
+    }
+
+    let indent = "    ".repeat(depth);
+    let _ = writeln!(kdl, "{indent}settings {{");
+    kdl.push_str(&nodes);
+    let _ = writeln!(kdl, "{indent}}}");
+}
+
+fn write_application(kdl: &mut String, application: &Application) {
+    let _ = writeln!(kdl, "application {} {{", quote(&application.name));
+    if let Some(description) = &application.description {
+        write_node(kdl, 1, "description", quote(description));
+    }
+    if let Some(spawn) = &application.spawn {
+        let args = spawn.iter().map(|arg| quote(arg)).collect::<Vec<_>>();
+        write_node(kdl, 1, "spawn", args.join(" "));
+    }
+    if let Some(spawn_line) = &application.spawn_line {
+        write_node(kdl, 1, "spawn-line", quote(spawn_line));
+    }
+    if let Some(spawn_sh) = &application.spawn_sh {
+        write_node(kdl, 1, "spawn-sh", quote(spawn_sh));
+    }
+    let spawn_scope = match application.spawn_scope {
+        SpawnScope::None => "none",
+        SpawnScope::Systemd => "systemd",
+    };
+    write_node(kdl, 1, "spawn-scope", quote(spawn_scope));
+    let on_focused = match application.on_focused {
+        OnFocused::Hide => "hide",
+        OnFocused::Launch => "launch",
+        OnFocused::Nothing => "nothing",
+    };
+    write_node(kdl, 1, "on-focused", quote(on_focused));
+    if application.autostart {
+        write_node(kdl, 1, "autostart", true);
+    }
+    write_rules(kdl, &application.matches, &application.excludes, 1);
+    for name in &application.match_refs {
+        write_node(kdl, 1, "match-ref", quote(name));
+    }
+    for name in &application.exclude_refs {
+        write_node(kdl, 1, "exclude-ref", quote(name));
+    }
+    for group in &application.groups {
+        write_group(kdl, group);
+    }
+    write_settings(kdl, &application.settings, 1);
+    kdl.push_str("}\n");
+}
+
+fn write_match_set(kdl: &mut String, match_set: &MatchSet) {
+    let _ = writeln!(kdl, "match-set {} {{", quote(&match_set.name));
+    for rule in &match_set.rules {
+        write_node(kdl, 1, "rule", rule_properties(rule));
+    }
+    kdl.push_str("}\n");
+}
+
+fn write_group(kdl: &mut String, group: &MatchGroup) {
+    kdl.push_str("    group {\n");
+    write_rules(kdl, &group.matches, &group.excludes, 2);
+    kdl.push_str("    }\n");
+}
+
+fn write_rules(kdl: &mut String, matches: &[MatchRule], excludes: &[MatchRule], depth: usize) {
+    for rule in matches {
+        write_node(kdl, depth, "match", rule_properties(rule));
+    }
+    for rule in excludes {
+        write_node(kdl, depth, "exclude", rule_properties(rule));
+    }
+}
+
+fn rule_properties(rule: &MatchRule) -> String {
+    let mut properties = vec![];
+    if let Some(id) = rule.id {
+        properties.push(format!("id={id}"));
+    }
+    if let Some(app_id) = &rule.app_id {
+        properties.push(format!("app-id={}", quote(app_id.as_str())));
+    }
+    if let Some(title) = &rule.title {
+        properties.push(format!("title={}", quote(title.as_str())));
+    }
+    if let Some(is_focused) = rule.is_focused {
+        properties.push(format!("is-focused={is_focused}"));
+    }
+    if let Some(is_floating) = rule.is_floating {
+        properties.push(format!("is-floating={is_floating}"));
+    }
+    if let Some(is_urgent) = rule.is_urgent {
+        properties.push(format!("is-urgent={is_urgent}"));
+    }
+    match rule.index {
+        Some(WindowIndex::First) => properties.push("index=\"first\"".to_string()),
+        Some(WindowIndex::Last) => properties.push("index=\"last\"".to_string()),
+        Some(WindowIndex::Nth(index)) => properties.push(format!("index={index}")),
+        None => {}
+    }
+    if let Some(limit) = rule.limit {
+        properties.push(format!("limit={limit}"));
+    }
+    for (name, value) in [
+        ("min-width", rule.min_width),
+        ("max-width", rule.max_width),
+        ("min-height", rule.min_height),
+        ("max-height", rule.max_height),
+    ] {
+        if let Some(value) = value {
+            properties.push(format!("{name}={value}"));
+        }
+    }
+    if rule.case_sensitive {
+        properties.push("case-sensitive=true".to_string());
+    }
+    if rule.require == RequireMode::Any {
+        properties.push("require=\"any\"".to_string());
+    }
+
+    properties.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        Config::from_str(
+            "config.toml",
+            r#"
+            [settings]
+            hidden-workspace = "^stash$"
+
+            [[application]]
+            name = "Telegram"
+            spawn = ["telegram-desktop", "-- \"quoted\""]
+
+            [[application.match]]
+            app-id = 'org\.telegram'
+            index = "last"
+
+            [[application.exclude]]
+            title = "Media viewer"
+            require = "any"
+
+            [application.settings]
+            launch-timeout-ms = 10000
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn exports_resolved_config_as_kdl() {
+        let kdl = to_kdl(&config().resolved());
+
+        assert_eq!(
+            kdl,
+            r#"settings {
+    shell "sh"
+    launch-timeout-ms 5000
+    hidden-workspace "^stash$"
+    inherit-io false
+    preserve-floating false
+    verify-focus false
+    verify false
+    autostart-delay-ms 0
+    multi "error"
+}
+application "Telegram" {
+    spawn "telegram-desktop" "-- \"quoted\""
+    spawn-scope "none"
+    on-focused "hide"
+    match app-id="org\\.telegram" index="last"
+    exclude title="Media viewer" require="any"
+    settings {
+        shell "sh"
+        launch-timeout-ms 10000
+        hidden-workspace "^stash$"
+        inherit-io false
+        preserve-floating false
+        verify-focus false
+        verify false
+        autostart-delay-ms 0
+        multi "error"
+    }
+}
+"#
+        );
+    }
+
+    #[test]
+    fn exports_patterns_as_source_strings_in_json() {
+        let json = serde_json::to_value(config().resolved()).unwrap();
+
+        let application = &json["application"][0];
+        assert_eq!(application["match"][0]["app-id"], r"org\.telegram");
+        assert_eq!(application["match"][0]["index"], "last");
+        assert_eq!(application["settings"]["hidden-workspace"], "^stash$");
+    }
+}
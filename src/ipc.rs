@@ -0,0 +1,219 @@
+use std::{
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+use miette::Result;
+use niri_ipc::{Request, Response, socket::Socket};
+
+use crate::{error::AppError, profile};
+
+/// Abstraction over the Niri IPC connection, so that actions can run against a fake in tests.
+pub trait NiriIpc {
+    /// Sends a request to Niri and returns its response. Errors reported by Niri itself are
+    /// turned into diagnostics as well.
+    fn send(&mut self, request: Request) -> Result<Response>;
+}
+
+impl NiriIpc for Socket {
+    fn send(&mut self, request: Request) -> Result<Response> {
+        // Only describe the request when profiling, it is sent on every command
+        let reply = if profile::enabled() {
+            let phase = format!("request {request:?}");
+            profile::time(phase, || Socket::send(self, request))
+        } else {
+            Socket::send(self, request)
+        };
+        let reply = reply.map_err(|err| {
+            AppError::IpcFailed(format!("Failed to communicate with Niri: {err}"))
+        })?;
+
+        Ok(reply.map_err(|err| AppError::IpcFailed(format!("Niri returned an error: {err}")))?)
+    }
+}
+
+/// Connects to the Niri IPC socket at `path`, or auto-discovers it via `$NIRI_SOCKET`.
+pub fn connect(path: Option<&Path>) -> Result<Socket> {
+    profile::time("connect", || {
+        let socket = match path {
+            Some(path) => Socket::connect_to(path).map_err(|err| {
+                AppError::IpcFailed(format!(
+                    "Failed to connect to Niri socket at {path:?}: {err}"
+                ))
+            })?,
+            None => Socket::connect().map_err(|err| {
+                AppError::IpcFailed(format!("Failed to connect to Niri socket: {err}"))
+            })?,
+        };
+
+        Ok(socket)
+    })
+}
+
+/// Like [`connect`], but keeps retrying with a growing interval until Niri accepts the
+/// connection or `timeout` expires, e.g. when running at login before Niri is up.
+pub fn connect_within(path: Option<&Path>, timeout: Duration) -> Result<Socket> {
+    let start = Instant::now();
+    let mut interval = Duration::from_millis(50);
+    loop {
+        let result = connect(path);
+        let remaining = timeout.saturating_sub(start.elapsed());
+        if result.is_ok() || remaining.is_zero() {
+            return result;
+        }
+
+        thread::sleep(interval.min(remaining));
+        interval = (interval * 2).min(Duration::from_secs(1));
+    }
+}
+
+/// Connection to Niri that is only established once the first request is sent, and
+/// re-established after a request fails.
+pub struct LazySocket {
+    path: Option<PathBuf>,
+    wait_ready: Duration,
+    socket: Option<Socket>,
+}
+
+impl LazySocket {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            wait_ready: Duration::ZERO,
+            socket: None,
+        }
+    }
+
+    /// Waits up to `timeout` for Niri to accept the connection, see [`connect_within`].
+    pub fn wait_ready(self, timeout: Duration) -> Self {
+        Self {
+            wait_ready: timeout,
+            ..self
+        }
+    }
+}
+
+impl NiriIpc for LazySocket {
+    fn send(&mut self, request: Request) -> Result<Response> {
+        let socket = match &mut self.socket {
+            Some(socket) => socket,
+            None => self
+                .socket
+                .insert(connect_within(self.path.as_deref(), self.wait_ready)?),
+        };
+
+        let response = NiriIpc::send(socket, request);
+        if response.is_err() {
+            // The connection may be broken, e.g. because Niri restarted
+            self.socket = None;
+        }
+
+        response
+    }
+}
+
+#[cfg(test)]
+pub mod fake {
+    use miette::Result;
+    use niri_ipc::{Request, Response, Window, Workspace};
+    use serde_json::json;
+
+    use super::NiriIpc;
+
+    /// In-memory Niri that answers window/workspace queries with canned data and records
+    /// every request it receives.
+    #[derive(Default)]
+    pub struct FakeIpc {
+        pub windows: Vec<Window>,
+        pub workspaces: Vec<Workspace>,
+        pub requests: Vec<Request>,
+    }
+
+    impl FakeIpc {
+        pub fn new(windows: Vec<Window>, workspaces: Vec<Workspace>) -> Self {
+            Self {
+                windows,
+                workspaces,
+                requests: vec![],
+            }
+        }
+
+        /// Returns the requests that are not window/workspace queries.
+        pub fn actions(&self) -> Vec<&Request> {
+            self.requests
+                .iter()
+                .filter(|request| {
+                    !matches!(request, Request::Windows | Request::WorkspacesWithHidden)
+                })
+                .collect()
+        }
+    }
+
+    impl NiriIpc for FakeIpc {
+        fn send(&mut self, request: Request) -> Result<Response> {
+            let response = match &request {
+                Request::Windows => Response::Windows(self.windows.clone()),
+                Request::WorkspacesWithHidden => Response::Workspaces(self.workspaces.clone()),
+                _ => Response::Handled,
+            };
+            self.requests.push(request);
+
+            Ok(response)
+        }
+    }
+
+    pub fn window(id: u64, app_id: &str, workspace_id: u64, is_focused: bool) -> Window {
+        serde_json::from_value(json!({
+            "id": id,
+            "title": format!("{app_id} window {id}"),
+            "app_id": app_id,
+            "pid": id * 100,
+            "workspace_id": workspace_id,
+            "is_focused": is_focused,
+            "is_floating": false,
+            "is_urgent": false,
+            "layout": {
+                "pos_in_scrolling_layout": null,
+                "tile_size": [800.0, 600.0],
+                "window_size": [800, 600],
+                "tile_pos_in_workspace_view": null,
+                "window_offset_in_tile": [0.0, 0.0],
+            },
+        }))
+        .unwrap()
+    }
+
+    pub fn workspace(id: u64, is_focused: bool, is_hidden: bool) -> Workspace {
+        serde_json::from_value(json!({
+            "id": id,
+            "idx": id,
+            "name": null,
+            "output": "DP-1",
+            "is_urgent": false,
+            "is_active": is_focused,
+            "is_focused": is_focused,
+            "is_hidden": is_hidden,
+            "active_window_id": null,
+        }))
+        .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connect_within_retries_until_timeout() {
+        let start = Instant::now();
+
+        let result = connect_within(
+            Some(Path::new("/nonexistent/niri.sock")),
+            Duration::from_millis(100),
+        );
+
+        assert!(result.is_err());
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+}
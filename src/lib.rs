@@ -0,0 +1,262 @@
+//! Launches, shows, hides and toggles application windows in the Niri Wayland
+//! compositor.
+//!
+//! Besides the `niri-app-hotkey` binary, the crate can be used as a library: load a
+//! [`Config`](config::Config), look up an application and call one of the functions in
+//! [`action`] with a connection to Niri. Actions accept any [`NiriIpc`] implementation, so
+//! that the connection can be shared or replaced.
+//!
+//! ```no_run
+//! use std::path::PathBuf;
+//!
+//! use niri_app_hotkey::{action, config::Config, ipc::LazySocket};
+//!
+//! let config = Config::parse(&PathBuf::from("/home/user/.config/niri/niri-app-hotkey.kdl"))?;
+//! let application = config.find_application("Telegram")?;
+//! let settings = config.settings_for(application);
+//!
+//! // Connects via `$NIRI_SOCKET` when the first request is sent
+//! let mut socket = LazySocket::new(None);
+//! action::toggle(&mut socket, application, &settings, false, action::AfterHide::Default)?;
+//! # Ok::<(), miette::Report>(())
+//! ```
+
+use miette::{Context, Result};
+use niri_ipc::{Action, Request};
+
+pub use crate::ipc::NiriIpc;
+
+pub mod action;
+mod cache;
+mod cli;
+pub mod config;
+mod cooldown;
+mod daemon;
+mod dispatch;
+mod doctor;
+mod editor;
+pub mod error;
+mod export;
+pub mod ipc;
+mod log;
+pub mod matching;
+mod migrate;
+mod notify;
+mod profile;
+mod prompt;
+mod query;
+mod report;
+mod table;
+
+/// Runs the command-line interface with the arguments of the current process.
+pub fn run() -> Result<()> {
+    let cli = cli::Cli::parse()?;
+    let notify = cli.notify;
+    if cli.profile {
+        profile::enable();
+    }
+
+    let result = profile::time("total", || execute(cli));
+    if notify && let Err(report) = &result {
+        notify::notify_error(report);
+    }
+
+    result
+}
+
+fn execute(cli: cli::Cli) -> Result<()> {
+    let load_config = || config::Config::parse_as(&cli.config_path, cli.config_format);
+    let connect = || ipc::connect_within(cli.socket_path.as_deref(), cli.wait_ready);
+    let daemon_socket_path = || {
+        cli.daemon_socket_path
+            .clone()
+            .map_or_else(daemon::default_socket_path, Ok)
+    };
+
+    match cli.command {
+        cli::Command::Init { force } => {
+            config::Config::init(&cli.config_path, cli.config_format, force)?;
+            println!("Created configuration file at: {:?}", cli.config_path);
+        }
+        cli::Command::Edit { no_validate } => {
+            editor::edit(&cli.config_path, cli.config_format)?;
+            if !no_validate {
+                load_config()?;
+                println!("Configuration file is valid.");
+            }
+        }
+        cli::Command::Validate { strict } => {
+            let config = load_config()?;
+            if config.applications.is_empty() {
+                eprintln!(
+                    "Warning: no applications are defined in {:?}, run `niri-app-hotkey init --force` to create a starter config",
+                    cli.config_path
+                );
+            }
+            if strict {
+                query::report_overlapping_rules(&config);
+                // Checks against the open windows are best-effort, so Niri being unreachable
+                // is not an error
+                if let Err(err) = connect().and_then(|mut socket| {
+                    query::report_unused_rules(&mut socket, &config)?;
+                    query::report_shared_windows(&mut socket, &config)
+                }) {
+                    eprintln!("Warning: skipping checks against open windows: {err}");
+                }
+            }
+            println!("Configuration file is valid.");
+        }
+        cli::Command::Reload => {
+            load_config().context("Not reloading the Niri configuration")?;
+            println!("Configuration file is valid.");
+            NiriIpc::send(&mut connect()?, Request::Action(Action::LoadConfigFile {}))
+                .context("Failed to ask Niri to reload its configuration")?;
+            println!("Niri configuration reloaded.");
+        }
+        cli::Command::Export { format } => {
+            export::export(&load_config()?, format)?;
+        }
+        cli::Command::Migrate { canonical } => {
+            migrate::migrate(&cli.config_path, cli.config_format, canonical)?;
+        }
+        cli::Command::Doctor => {
+            doctor::doctor(
+                &cli.config_path,
+                cli.config_format,
+                cli.socket_path.as_deref(),
+            )?;
+        }
+        cli::Command::Daemon {
+            ref log_file,
+            log_max_size,
+        } => {
+            let listen_path = daemon_socket_path()?;
+            let log = match log_file {
+                Some(path) => log::DaemonLog::open(path, log_max_size)?,
+                None => log::DaemonLog::default(),
+            };
+            daemon::serve(
+                &listen_path,
+                &cli.config_path,
+                cli.config_format,
+                cli.socket_path.clone(),
+                &cli.settings,
+                log,
+            )?;
+        }
+        command @ (cli::Command::Launch { .. }
+        | cli::Command::Start { .. }
+        | cli::Command::Show { .. }
+        | cli::Command::Hide { .. }
+        | cli::Command::Focus { .. }
+        | cli::Command::Activate { .. }
+        | cli::Command::Close { .. }
+        | cli::Command::Kill { .. }
+        | cli::Command::Toggle { .. }
+        | cli::Command::Raise { .. }) => {
+            // A printed command line belongs on the client's terminal, not the daemon's, and
+            // the delays between autostart launches would hold up the daemon's other clients
+            let runs_locally = matches!(
+                command,
+                cli::Command::Launch {
+                    print_command: true,
+                    ..
+                } | cli::Command::Start { .. }
+            );
+            let reports = if cli.client && !runs_locally {
+                let request = daemon::DaemonRequest {
+                    command,
+                    yes: cli.yes,
+                    match_index: cli.match_index,
+                    settings: cli.settings.clone(),
+                };
+                daemon::send(&daemon_socket_path()?, &request)?
+            } else {
+                // Ad-hoc targets work without a config file, but use its settings if present
+                let config = if command.is_ad_hoc()
+                    && !cli.config_path.exists()
+                    && !config::is_stdin(&cli.config_path)
+                {
+                    config::Config::default()
+                } else {
+                    load_config()?
+                };
+                let mut socket =
+                    ipc::LazySocket::new(cli.socket_path.clone()).wait_ready(cli.wait_ready);
+                dispatch::run_application_command(
+                    command,
+                    &config,
+                    &mut socket,
+                    &cli.settings,
+                    cli.match_index,
+                    &cooldown::Cooldowns::in_runtime_dir(),
+                    |windows| Ok(cli.yes || prompt::confirm_windows("close", windows)?),
+                )?
+            };
+            for report in reports {
+                if cli.print_id {
+                    println!("{}", report.id);
+                } else if !cli.quiet {
+                    println!("{}", report.message);
+                }
+            }
+        }
+        cli::Command::List { only_running } => {
+            let config = load_config()?;
+            // Listing the configuration does not require Niri to be running
+            let windows =
+                match connect().and_then(|mut socket| query::get_sorted_windows(&mut socket)) {
+                    Ok(windows) => Some(windows),
+                    Err(err) => {
+                        eprintln!("Warning: could not query open windows: {err}");
+                        if only_running {
+                            eprintln!("Warning: listing all applications instead of running ones");
+                        }
+                        None
+                    }
+                };
+            query::applications(&config, windows.as_deref(), only_running);
+        }
+        cli::Command::Match {
+            app_id,
+            title,
+            index,
+            case_sensitive,
+            json,
+        } => {
+            let rule = config::MatchRule::parse(
+                app_id.as_deref(),
+                title.as_deref(),
+                index.as_deref(),
+                case_sensitive,
+            )?;
+            query::match_rule(&mut connect()?, rule, json)?;
+        }
+        cli::Command::Which { window_id } => {
+            let config = load_config()?;
+            query::which(&mut connect()?, &config, window_id)?;
+        }
+        cli::Command::Windows { json, watch } => {
+            if watch {
+                query::watch_windows(connect()?)?;
+            } else {
+                query::windows(&mut connect()?, json)?;
+            }
+        }
+        cli::Command::Ping { count } => {
+            query::ping(&mut connect()?, count)?;
+        }
+        cli::Command::Version => {
+            println!("niri-app-hotkey {}", cli::LONG_VERSION);
+            match connect().and_then(|mut socket| query::niri_version(&mut socket)) {
+                Ok(version) => println!("niri {version}"),
+                Err(err) => eprintln!("Warning: could not query the running Niri version: {err}"),
+            }
+        }
+        cli::Command::Workspaces { json } => {
+            query::workspaces(&mut connect()?, json)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,178 @@
+use std::{
+    fmt::Display,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use miette::{Context, IntoDiagnostic, Result};
+use niri_ipc::{Request, Response};
+
+use crate::ipc::NiriIpc;
+
+/// Log of what the daemon does, written to a file so that misbehavior can be traced after
+/// the fact. Without a file, writing to it does nothing.
+#[derive(Default)]
+pub struct DaemonLog {
+    file: Option<Mutex<LogFile>>,
+}
+
+struct LogFile {
+    path: PathBuf,
+    max_size: u64,
+    file: File,
+    size: u64,
+}
+
+impl DaemonLog {
+    /// Appends to the file at `path`. Once it would grow beyond `max_size` bytes, it is
+    /// renamed to `<path>.1`, replacing the previous one, and a new file is started.
+    pub fn open(path: &Path, max_size: u64) -> Result<Self> {
+        let file = open_append(path)?;
+        let size = file.metadata().map_or(0, |metadata| metadata.len());
+
+        Ok(Self {
+            file: Some(Mutex::new(LogFile {
+                path: path.to_path_buf(),
+                max_size,
+                file,
+                size,
+            })),
+        })
+    }
+
+    /// Writes a timestamped line. Failing to write is reported on stderr and otherwise
+    /// ignored, so that logging never gets in the way of handling requests.
+    pub fn write(&self, message: impl Display) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        let mut file = file.lock().unwrap_or_else(|err| err.into_inner());
+        let line = format!("{} {message}\n", format_timestamp(SystemTime::now()));
+        if let Err(err) = file.append(&line) {
+            eprintln!(
+                "Warning: failed to write to log file {:?}: {err}",
+                file.path
+            );
+        }
+    }
+}
+
+impl LogFile {
+    fn append(&mut self, line: &str) -> Result<()> {
+        let len = line.len() as u64;
+        if self.size > 0 && self.size + len > self.max_size {
+            let mut rotated = self.path.as_os_str().to_owned();
+            rotated.push(".1");
+            fs::rename(&self.path, &rotated)
+                .into_diagnostic()
+                .context("Failed to rotate log file")?;
+            self.file = open_append(&self.path)?;
+            self.size = 0;
+        }
+
+        self.file.write_all(line.as_bytes()).into_diagnostic()?;
+        self.size += len;
+
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .into_diagnostic()
+        .context(format!("Failed to open log file at {path:?}"))
+}
+
+/// Formats the time as UTC in RFC 3339 with milliseconds, e.g. `2025-01-31T08:00:00.000Z`.
+fn format_timestamp(time: SystemTime) -> String {
+    let elapsed = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = elapsed.as_secs();
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+
+    // Converts days since the epoch to a civil date, see
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        elapsed.subsec_millis()
+    )
+}
+
+/// Passes requests on to Niri and writes the actions among them to a [`DaemonLog`], which
+/// also shows which window a command matched.
+pub struct LoggedSocket<'a, S> {
+    socket: &'a mut S,
+    log: &'a DaemonLog,
+}
+
+impl<'a, S: NiriIpc> LoggedSocket<'a, S> {
+    pub fn new(socket: &'a mut S, log: &'a DaemonLog) -> Self {
+        Self { socket, log }
+    }
+}
+
+impl<S: NiriIpc> NiriIpc for LoggedSocket<'_, S> {
+    fn send(&mut self, request: Request) -> Result<Response> {
+        if let Request::Action(action) = &request {
+            self.log.write(format_args!("action: {action:?}"));
+        }
+
+        self.socket.send(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn format_timestamp_prints_utc_date_and_time() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_210_096_789);
+
+        assert_eq!(format_timestamp(time), "2024-02-29T12:34:56.789Z");
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn log_rotates_when_exceeding_max_size() {
+        let dir = env::temp_dir().join(format!("niri-app-hotkey-log-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("daemon.log");
+
+        let log = DaemonLog::open(&path, 64).unwrap();
+        log.write("first request");
+        log.write("second request");
+
+        let current = fs::read_to_string(&path).unwrap();
+        let rotated = fs::read_to_string(dir.join("daemon.log.1")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(rotated.ends_with(" first request\n"), "{rotated}");
+        assert!(current.ends_with(" second request\n"), "{current}");
+        assert_eq!(current.lines().count(), 1);
+    }
+}
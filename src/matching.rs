@@ -0,0 +1,512 @@
+use std::{cmp::Ordering, collections::HashSet};
+
+use miette::{Result, bail};
+use niri_ipc::Window;
+
+use crate::{
+    config::{Application, MatchRule, MultiPolicy, Regex, RequireMode, Settings},
+    error::AppError,
+    profile,
+};
+
+/// Checks the properties set on a rule against a window. Depending on the rule's
+/// `require` mode, a window must match all of them or just one. A property the window lacks,
+/// such as a missing title, never matches. Size bounds must always hold.
+pub fn is_window_match_rule(window: &Window, rule: &MatchRule) -> bool {
+    let within = |value: i32, min: Option<u32>, max: Option<u32>| {
+        min.is_none_or(|min| i64::from(value) >= i64::from(min))
+            && max.is_none_or(|max| i64::from(value) <= i64::from(max))
+    };
+    let (width, height) = window.layout.window_size;
+    if !within(width, rule.min_width, rule.max_width)
+        || !within(height, rule.min_height, rule.max_height)
+    {
+        return false;
+    }
+
+    let matches_regex = |regex: &Regex, value: &Option<String>| {
+        value.as_ref().is_some_and(|value| regex.is_match(value))
+    };
+    let mut properties = [
+        rule.id.map(|id| id == window.id),
+        rule.app_id
+            .as_ref()
+            .map(|app_id| matches_regex(app_id, &window.app_id)),
+        rule.is_focused
+            .map(|is_focused| is_focused == window.is_focused),
+        rule.is_floating
+            .map(|is_floating| is_floating == window.is_floating),
+        rule.is_urgent
+            .map(|is_urgent| is_urgent == window.is_urgent),
+        rule.title
+            .as_ref()
+            .map(|title| matches_regex(title, &window.title)),
+    ]
+    .into_iter()
+    .flatten()
+    .peekable();
+
+    match rule.require {
+        RequireMode::All => properties.all(|matched| matched),
+        // A rule without properties matches every window, as in `All` mode
+        RequireMode::Any => properties.peek().is_none() || properties.any(|matched| matched),
+    }
+}
+
+/// Orders windows by PID. Windows without a PID keep their relative order.
+fn compare_pids(a: &Window, b: &Window) -> Ordering {
+    match (a.pid, b.pid) {
+        (Some(a_pid), Some(b_pid)) => a_pid.cmp(&b_pid),
+        _ => Ordering::Equal,
+    }
+}
+
+/// Returns the IDs of the windows selected by any of the rules. Each rule is evaluated on
+/// its own: the windows matching its properties are sorted by PID, then narrowed down to
+/// the one at `index`, or to the first `limit` windows. An `index` beyond the candidates of
+/// its rule selects nothing, so that rule has no effect.
+fn select_window_ids(windows: &[Window], rules: &[MatchRule]) -> HashSet<u64> {
+    let mut selected_window_ids = HashSet::new();
+    for rule in rules {
+        let mut matched_windows = windows
+            .iter()
+            .filter(|window| is_window_match_rule(window, rule))
+            .collect::<Vec<_>>();
+        matched_windows.sort_by(|a, b| compare_pids(a, b));
+
+        let mut selected_windows = match rule.index {
+            Some(index) => index
+                .resolve(matched_windows.len())
+                .map(|index| vec![matched_windows[index].id])
+                .unwrap_or_default(),
+            None => matched_windows.iter().map(|window| window.id).collect(),
+        };
+        if let Some(limit) = rule.limit {
+            selected_windows.truncate(limit);
+        }
+        selected_window_ids.extend(selected_windows);
+    }
+
+    selected_window_ids
+}
+
+/// Returns all windows targeted by the given rules, without talking to Niri.
+///
+/// A window is targeted if any `match` rule selects it and no `exclude` rule does. Both
+/// kinds of rules select windows the same way: the windows matching a rule's properties
+/// are sorted by PID, then narrowed down by its `index` or `limit`.
+pub fn match_windows<'a>(
+    windows: &'a [Window],
+    matches: &[MatchRule],
+    excludes: &[MatchRule],
+) -> Vec<&'a Window> {
+    let included_window_ids = select_window_ids(windows, matches);
+    let excluded_window_ids = select_window_ids(windows, excludes);
+
+    windows
+        .iter()
+        .filter(|window| {
+            included_window_ids.contains(&window.id) && !excluded_window_ids.contains(&window.id)
+        })
+        .collect()
+}
+
+/// Selects the single window targeted by the given rules. Returns `None` if no window is
+/// targeted. If more than one is, returns the one with the lowest PID with `first_match`,
+/// and an error otherwise.
+pub fn select_window(
+    windows: &[Window],
+    matches: &[MatchRule],
+    excludes: &[MatchRule],
+    first_match: bool,
+) -> Result<Option<u64>> {
+    let matched_windows = match_windows(windows, matches, excludes);
+
+    // Check results
+    if matched_windows.len() > 1 {
+        if first_match {
+            let first_window = matched_windows
+                .iter()
+                .min_by(|a, b| compare_pids(a, b))
+                .map(|window| window.id);
+            return Ok(first_window);
+        }
+        bail!(AppError::MultipleMatches(
+            matched_windows.into_iter().cloned().collect()
+        ));
+    }
+
+    Ok(matched_windows.first().map(|window| window.id))
+}
+
+/// Returns all windows targeted by the first rule set of the application that targets any.
+pub fn match_application_windows<'a>(
+    windows: &'a [Window],
+    application: &Application,
+) -> Vec<&'a Window> {
+    application
+        .rule_sets()
+        .map(|(matches, excludes)| match_windows(windows, matches, excludes))
+        .find(|matched_windows| !matched_windows.is_empty())
+        .unwrap_or_default()
+}
+
+/// Selects the single window targeted by the first rule set of the application that
+/// targets any. Later rule sets are not consulted once one targets a window, so an
+/// ambiguous match is still an error.
+pub fn select_application_window(
+    windows: &[Window],
+    application: &Application,
+    settings: &Settings,
+) -> Result<Option<u64>> {
+    profile::time("match", || {
+        for (matches, excludes) in application.rule_sets() {
+            if let Some(window_id) = select_window(
+                windows,
+                matches,
+                excludes,
+                settings.multi() == MultiPolicy::First,
+            )? {
+                return Ok(Some(window_id));
+            }
+        }
+
+        Ok(None)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use crate::{
+        config::{MatchGroup, Regex, WindowIndex},
+        ipc::fake::window,
+    };
+
+    fn rule(app_id: Option<&str>, title: Option<&str>, index: Option<WindowIndex>) -> MatchRule {
+        MatchRule {
+            app_id: app_id.map(|app_id| Regex::from_str(app_id).unwrap()),
+            title: title.map(|title| Regex::from_str(title).unwrap()),
+            index,
+            ..Default::default()
+        }
+    }
+
+    fn windows() -> Vec<Window> {
+        vec![
+            window(1, "firefox", 1, false),
+            window(2, "kitty", 1, true),
+            window(3, "kitty", 2, false),
+            window(4, "kitty", 3, false),
+        ]
+    }
+
+    #[test]
+    fn selects_single_match() {
+        let matches = [rule(Some("firefox"), None, None)];
+
+        assert_eq!(
+            select_window(&windows(), &matches, &[], false).unwrap(),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn selects_nothing_without_match() {
+        let matches = [rule(Some("telegram"), None, None)];
+
+        assert_eq!(
+            select_window(&windows(), &matches, &[], false).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_multiple_matches() {
+        let matches = [rule(Some("kitty"), None, None)];
+
+        let err = select_window(&windows(), &matches, &[], false).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(AppError::MultipleMatches(windows)) if windows.len() == 3
+        ));
+    }
+
+    #[test]
+    fn first_match_picks_lowest_pid() {
+        let mut windows = windows();
+        windows.reverse();
+        let matches = [rule(Some("kitty"), None, None)];
+
+        assert_eq!(
+            select_window(&windows, &matches, &[], true).unwrap(),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn rejects_matches_from_different_rules() {
+        let matches = [
+            rule(Some("firefox"), None, None),
+            rule(None, Some("kitty window 2"), None),
+        ];
+
+        assert!(select_window(&windows(), &matches, &[], false).is_err());
+    }
+
+    #[test]
+    fn requires_all_properties_of_a_rule() {
+        let matches = [rule(Some("kitty"), Some("window 3"), None)];
+
+        assert_eq!(
+            select_window(&windows(), &matches, &[], false).unwrap(),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn excludes_remove_included_windows() {
+        let matches = [rule(Some("kitty"), None, None)];
+        let excludes = [rule(None, Some("window [24]"), None)];
+
+        assert_eq!(
+            select_window(&windows(), &matches, &excludes, false).unwrap(),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn is_focused_narrows_down_matches() {
+        let matches = [MatchRule {
+            is_focused: Some(true),
+            ..rule(Some("kitty"), None, None)
+        }];
+
+        assert_eq!(
+            select_window(&windows(), &matches, &[], false).unwrap(),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn is_floating_excludes_tiled_windows() {
+        let mut windows = windows();
+        windows[2].is_floating = true;
+        let matches = [MatchRule {
+            is_floating: Some(true),
+            ..rule(Some("kitty"), None, None)
+        }];
+
+        assert_eq!(
+            select_window(&windows, &matches, &[], false).unwrap(),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn is_urgent_combines_with_other_properties() {
+        let mut windows = windows();
+        windows[0].is_urgent = true;
+        windows[2].is_urgent = true;
+        let matches = [MatchRule {
+            is_urgent: Some(true),
+            ..rule(Some("kitty"), None, None)
+        }];
+
+        assert_eq!(
+            select_window(&windows, &matches, &[], false).unwrap(),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn require_any_matches_on_a_single_property() {
+        let mut windows = windows();
+        windows[0].title = None;
+        let rule = MatchRule {
+            require: RequireMode::Any,
+            ..rule(Some("firefox"), Some("^kitty window 3$"), None)
+        };
+
+        let matched_windows = windows
+            .iter()
+            .filter(|window| is_window_match_rule(window, &rule))
+            .map(|window| window.id)
+            .collect::<Vec<_>>();
+        assert_eq!(matched_windows, [1, 3]);
+        assert!(!is_window_match_rule(
+            &windows[0],
+            &MatchRule {
+                require: RequireMode::All,
+                ..rule
+            }
+        ));
+    }
+
+    #[test]
+    fn size_bounds_apply_in_any_require_mode() {
+        let mut windows = windows();
+        windows[2].layout.window_size = (320, 180);
+        let small = |require| MatchRule {
+            max_width: Some(400),
+            max_height: Some(300),
+            require,
+            ..rule(Some("kitty"), None, None)
+        };
+
+        for require in [RequireMode::All, RequireMode::Any] {
+            let matched_windows = windows
+                .iter()
+                .filter(|window| is_window_match_rule(window, &small(require)))
+                .map(|window| window.id)
+                .collect::<Vec<_>>();
+            assert_eq!(matched_windows, [3], "{require:?}");
+        }
+        assert!(is_window_match_rule(
+            &windows[0],
+            &MatchRule {
+                min_width: Some(800),
+                min_height: Some(600),
+                ..Default::default()
+            }
+        ));
+    }
+
+    #[test]
+    fn excludes_window_by_id() {
+        let matches = [rule(Some("kitty"), None, None)];
+        let excludes = [MatchRule {
+            id: Some(3),
+            ..Default::default()
+        }];
+
+        let matched_windows = match_windows(&windows(), &matches, &excludes)
+            .iter()
+            .map(|window| window.id)
+            .collect::<Vec<_>>();
+        assert_eq!(matched_windows, [2, 4]);
+    }
+
+    #[test]
+    fn index_selects_by_pid_order() {
+        let mut windows = windows();
+        windows.reverse();
+
+        for (index, expected) in [
+            (WindowIndex::Nth(0), Some(2)),
+            (WindowIndex::Nth(1), Some(3)),
+            (WindowIndex::First, Some(2)),
+            (WindowIndex::Last, Some(4)),
+        ] {
+            let matches = [rule(Some("kitty"), None, Some(index))];
+
+            assert_eq!(
+                select_window(&windows, &matches, &[], false).unwrap(),
+                expected,
+                "{index:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn index_applies_to_excludes() {
+        let matches = [rule(Some("kitty"), None, None)];
+        let excludes = [
+            rule(Some("kitty"), None, Some(WindowIndex::First)),
+            rule(Some("kitty"), None, Some(WindowIndex::Last)),
+        ];
+
+        assert_eq!(
+            select_window(&windows(), &matches, &excludes, false).unwrap(),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn out_of_range_index_selects_nothing() {
+        let matches = [rule(Some("kitty"), None, Some(WindowIndex::Nth(3)))];
+        assert_eq!(
+            select_window(&windows(), &matches, &[], false).unwrap(),
+            None
+        );
+
+        // The index is resolved against the candidates of its own rule, not all windows
+        let matches = [rule(Some("firefox"), None, Some(WindowIndex::Nth(1)))];
+        assert_eq!(
+            select_window(&windows(), &matches, &[], false).unwrap(),
+            None
+        );
+
+        let matches = [rule(Some("firefox"), None, None)];
+        let excludes = [rule(Some("firefox"), None, Some(WindowIndex::Nth(1)))];
+        assert_eq!(
+            select_window(&windows(), &matches, &excludes, false).unwrap(),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn limit_caps_matched_windows_by_pid_order() {
+        let matches = [MatchRule {
+            limit: Some(2),
+            ..rule(Some("kitty"), None, None)
+        }];
+
+        let matched_windows = match_windows(&windows(), &matches, &[])
+            .iter()
+            .map(|window| window.id)
+            .collect::<Vec<_>>();
+        assert_eq!(matched_windows, [2, 3]);
+        assert!(select_window(&windows(), &matches, &[], false).is_err());
+    }
+
+    #[test]
+    fn first_group_with_a_match_wins() {
+        let application = Application {
+            name: "browser".to_string(),
+            groups: vec![
+                MatchGroup {
+                    matches: vec![rule(Some("chromium"), None, None)],
+                    ..Default::default()
+                },
+                MatchGroup {
+                    matches: vec![rule(Some("firefox"), None, None)],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            select_application_window(&windows(), &application, &Settings::default()).unwrap(),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn own_rules_take_precedence_over_groups() {
+        let application = Application {
+            name: "kitty".to_string(),
+            matches: vec![rule(Some("kitty"), None, Some(WindowIndex::First))],
+            groups: vec![MatchGroup {
+                matches: vec![rule(Some("firefox"), None, None)],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            select_application_window(&windows(), &application, &Settings::default()).unwrap(),
+            Some(2)
+        );
+        assert_eq!(
+            match_application_windows(&windows(), &application)
+                .iter()
+                .map(|window| window.id)
+                .collect::<Vec<_>>(),
+            [2]
+        );
+    }
+}
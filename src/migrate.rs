@@ -0,0 +1,320 @@
+use std::{
+    fmt::{self, Display},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use miette::{Context, IntoDiagnostic, Result, bail, miette};
+use serde_yaml::Value;
+
+use crate::{
+    config::{self, CONFIG_VERSION, Config, ConfigFormat},
+    export,
+};
+
+/// Updates the config file at `path` to [`CONFIG_VERSION`], keeping a copy of the original
+/// next to it, and prints the lines that changed. With `canonical`, the whole file is
+/// rewritten in canonical form.
+pub fn migrate(path: &Path, format: ConfigFormat, canonical: bool) -> Result<()> {
+    if config::is_stdin(path) {
+        bail!("Cannot migrate a config read from stdin, pass a file with --config");
+    }
+    let name = path
+        .to_str()
+        .ok_or_else(|| miette!("Invalid config file name"))?;
+    let text = fs::read_to_string(path)
+        .into_diagnostic()
+        .context(format!("Failed to read config file at: {path:?}"))?;
+
+    let migrated = migrate_str(name, &text, format, canonical)?;
+    if migrated == text {
+        println!("Configuration file is already up to date.");
+        return Ok(());
+    }
+
+    let backup = backup_path(path);
+    fs::copy(path, &backup)
+        .into_diagnostic()
+        .context(format!("Failed to back up config file to: {backup:?}"))?;
+    fs::write(path, &migrated)
+        .into_diagnostic()
+        .context(format!("Failed to write config file at: {path:?}"))?;
+
+    let changes = diff_lines(&text, &migrated);
+    for change in &changes {
+        println!("{change}");
+    }
+    let removed = changes
+        .iter()
+        .filter(|change| matches!(change, Change::Removed(_)))
+        .count();
+    println!(
+        "Migrated configuration file to config-version {CONFIG_VERSION}, {removed} lines removed and {} added. The original is kept at: {backup:?}",
+        changes.len() - removed
+    );
+
+    Ok(())
+}
+
+/// Returns the config in `text` updated to [`CONFIG_VERSION`], in `format`. Only the
+/// `config-version` line is changed, unless `canonical` asks for the whole config to be
+/// written out anew, in which case comments are not carried over.
+fn migrate_str(name: &str, text: &str, format: ConfigFormat, canonical: bool) -> Result<String> {
+    let mut config = Config::decode(name, text, format)?;
+    if let Some(version) = config.config_version
+        && version > CONFIG_VERSION
+    {
+        bail!(
+            "config-version {version} is newer than version {CONFIG_VERSION} of this niri-app-hotkey, upgrade niri-app-hotkey instead"
+        );
+    }
+    // Schema changes add their upgrade steps for older versions here, which also need to
+    // rewrite the config as a whole
+    config.config_version = Some(CONFIG_VERSION);
+
+    let migrated = if !canonical {
+        set_version_line(text, format)
+    } else {
+        match format {
+            ConfigFormat::Kdl => export::to_kdl(&config),
+            ConfigFormat::Toml => toml::to_string(&to_value(&config)?).into_diagnostic()?,
+            ConfigFormat::Yaml => serde_yaml::to_string(&to_value(&config)?).into_diagnostic()?,
+        }
+    };
+    // A config that does not read back must never replace the original
+    Config::from_str_as(name, &migrated, format)
+        .context("Failed to migrate the configuration file")?;
+
+    Ok(migrated)
+}
+
+/// Sets `config-version` in `text` to [`CONFIG_VERSION`] by editing only that line, so that
+/// comments and the order of options are kept. Without a top-level `config-version`, one is
+/// added in front of the first option.
+fn set_version_line(text: &str, format: ConfigFormat) -> String {
+    let (version_line, comment) = match format {
+        ConfigFormat::Kdl => (format!("config-version {CONFIG_VERSION}"), "//"),
+        ConfigFormat::Toml => (format!("config-version = {CONFIG_VERSION}"), "#"),
+        ConfigFormat::Yaml => (format!("config-version: {CONFIG_VERSION}"), "#"),
+    };
+    // Nested options are indented, so only an unindented line sets the version
+    let is_version_line = |line: &str| {
+        line.strip_prefix("config-version")
+            .is_some_and(|rest| rest.starts_with([' ', '\t', '=', ':']))
+    };
+
+    let lines = text.split_inclusive('\n').collect::<Vec<_>>();
+    if let Some(index) = lines.iter().position(|line| is_version_line(line)) {
+        let ending = if lines[index].ends_with('\n') {
+            "\n"
+        } else {
+            ""
+        };
+        let mut migrated = lines[..index].concat();
+        migrated.push_str(&version_line);
+        migrated.push_str(ending);
+        migrated.push_str(&lines[index + 1..].concat());
+        return migrated;
+    }
+
+    // Leading comments and a YAML document marker stay in front of the version
+    let index = lines
+        .iter()
+        .position(|line| {
+            let line = line.trim();
+            !(line.is_empty() || line.starts_with(comment) || line == "---")
+        })
+        .unwrap_or(lines.len());
+    let mut migrated = lines[..index].concat();
+    if !migrated.is_empty() && !migrated.ends_with('\n') {
+        migrated.push('\n');
+    }
+    migrated.push_str(&version_line);
+    migrated.push_str("\n\n");
+    migrated.push_str(&lines[index..].concat());
+
+    migrated
+}
+
+/// Converts the config into a tree of values in field order, leaving out unset fields and
+/// empty lists and blocks.
+fn to_value(config: &Config) -> Result<Value> {
+    fn prune(value: &mut Value) -> bool {
+        match value {
+            Value::Null => return false,
+            Value::Sequence(values) => values.retain_mut(prune),
+            Value::Mapping(mapping) => mapping.retain(|_, value| prune(value)),
+            _ => return true,
+        }
+
+        match value {
+            Value::Sequence(values) => !values.is_empty(),
+            Value::Mapping(mapping) => !mapping.is_empty(),
+            _ => true,
+        }
+    }
+
+    let mut value = serde_yaml::to_value(config).into_diagnostic()?;
+    prune(&mut value);
+
+    Ok(value)
+}
+
+/// Returns `<path>.bak`, or if that exists already, the first of `<path>.bak.1`,
+/// `<path>.bak.2` and so on that does not, so that earlier backups are never overwritten.
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    let mut candidate = PathBuf::from(&backup);
+    let mut number = 0;
+    while candidate.exists() {
+        number += 1;
+        let mut numbered = backup.clone();
+        numbered.push(format!(".{number}"));
+        candidate = numbered.into();
+    }
+
+    candidate
+}
+
+/// A line that differs between the original and the migrated config.
+#[derive(Debug, PartialEq, Eq)]
+enum Change<'a> {
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+impl Display for Change<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Removed(line) => write!(f, "- {line}"),
+            Self::Added(line) => write!(f, "+ {line}"),
+        }
+    }
+}
+
+/// Lists the lines removed from `old` and added in `new`, in order, keeping the longest
+/// common subsequence of lines unchanged.
+fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<Change<'a>> {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+
+    // `common[i][j]` is the length of the longest common subsequence of `old[i..]` and
+    // `new[j..]`
+    let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for (i, old_line) in old.iter().enumerate().rev() {
+        for (j, new_line) in new.iter().enumerate().rev() {
+            common[i][j] = if old_line == new_line {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut changes = vec![];
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            changes.push(Change::Removed(old[i]));
+            i += 1;
+        } else {
+            changes.push(Change::Added(new[j]));
+            j += 1;
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    #[test]
+    fn backup_path_does_not_overwrite_earlier_backups() {
+        let dir = env::temp_dir().join(format!("niri-app-hotkey-migrate-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.kdl");
+
+        let first = backup_path(&path);
+        fs::write(&first, "").unwrap();
+        let second = backup_path(&path);
+        fs::write(&second, "").unwrap();
+        let third = backup_path(&path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(first, dir.join("config.kdl.bak"));
+        assert_eq!(second, dir.join("config.kdl.bak.1"));
+        assert_eq!(third, dir.join("config.kdl.bak.2"));
+    }
+
+    #[test]
+    fn migrate_str_adds_version_and_keeps_match_sets() {
+        let text = r#"
+            [[match-set]]
+            name = "browsers"
+            rule = [{ app-id = "firefox" }]
+
+            [[application]]
+            name = "Browser"
+            spawn = ["firefox"]
+            match-ref = ["browsers"]
+            "#;
+
+        let migrated = migrate_str("config.toml", text, ConfigFormat::Toml, true).unwrap();
+        let config = Config::decode("config.toml", &migrated, ConfigFormat::Toml).unwrap();
+
+        assert_eq!(config.config_version, Some(CONFIG_VERSION));
+        assert_eq!(config.match_sets[0].name, "browsers");
+        assert_eq!(config.applications[0].match_refs, ["browsers"]);
+        assert!(!migrated.contains("exclude"), "{migrated}");
+        assert_eq!(
+            migrate_str("config.toml", &migrated, ConfigFormat::Toml, true).unwrap(),
+            migrated
+        );
+    }
+
+    #[test]
+    fn migrate_str_keeps_comments_and_order() {
+        let text = "# Browsers\n[[application]]\nspawn = [\"firefox\"] # default profile\nname = \"Browser\"\n";
+        let outdated = "config-version: 0\nsettings:\n  config-version: 0\n";
+
+        let migrated = migrate_str("config.toml", text, ConfigFormat::Toml, false).unwrap();
+
+        assert_eq!(
+            migrated,
+            format!(
+                "# Browsers\nconfig-version = {CONFIG_VERSION}\n\n{}",
+                &text[11..]
+            )
+        );
+        assert_eq!(
+            migrate_str("config.toml", &migrated, ConfigFormat::Toml, false).unwrap(),
+            migrated
+        );
+        assert_eq!(
+            set_version_line(outdated, ConfigFormat::Yaml),
+            format!("config-version: {CONFIG_VERSION}\nsettings:\n  config-version: 0\n")
+        );
+    }
+
+    #[test]
+    fn migrate_str_rejects_newer_config_version() {
+        let text = format!("config-version: {}\n", CONFIG_VERSION + 1);
+
+        assert!(migrate_str("config.yaml", &text, ConfigFormat::Yaml, false).is_err());
+    }
+
+    #[test]
+    fn diff_lines_lists_removed_and_added_lines() {
+        let changes = diff_lines("a\nb\nc\n", "a\nc\nd\n");
+
+        assert_eq!(changes, [Change::Removed("b"), Change::Added("d")]);
+    }
+}
@@ -0,0 +1,29 @@
+use std::process::{Command, Stdio};
+
+use miette::Report;
+
+use crate::error::format_report;
+
+const NOTIFY_SEND: &str = "notify-send";
+
+/// Shows the error as a desktop notification via `notify-send`, since the output of a
+/// command run from a hotkey is not visible. A missing or failing notifier is reported on
+/// stderr and otherwise ignored.
+pub fn notify_error(report: &Report) {
+    let status = Command::new(NOTIFY_SEND)
+        .args([
+            "--app-name=niri-app-hotkey",
+            "--urgency=critical",
+            "niri-app-hotkey failed",
+        ])
+        .arg(format_report(report))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .status();
+
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => eprintln!("Warning: {NOTIFY_SEND} exited with {status}"),
+        Err(err) => eprintln!("Warning: could not run {NOTIFY_SEND} to show a notification: {err}"),
+    }
+}
@@ -0,0 +1,33 @@
+use std::{
+    fmt::Display,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Instant,
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turns on printing how long each timed phase takes.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Whether timed phases are printed, e.g. to skip describing a phase that is not.
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Runs `f` and, if profiling is enabled, prints to stderr how long it took.
+pub fn time<T>(phase: impl Display, f: impl FnOnce() -> T) -> T {
+    if !enabled() {
+        return f();
+    }
+
+    let start = Instant::now();
+    let result = f();
+    eprintln!(
+        "[profile] {phase}: {:.3} ms",
+        start.elapsed().as_secs_f64() * 1000.0
+    );
+
+    result
+}
@@ -0,0 +1,28 @@
+use std::io::{self, IsTerminal, Write};
+
+use miette::{IntoDiagnostic, Result};
+use niri_ipc::Window;
+
+/// Asks the user to confirm an action on several windows. Without an interactive terminal
+/// there is nobody to ask, so the action is confirmed.
+pub fn confirm_windows(action: &str, windows: &[&Window]) -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        return Ok(true);
+    }
+
+    eprintln!("About to {action} {} windows:", windows.len());
+    for window in windows {
+        eprintln!(
+            "  - {} (id {})",
+            window.title.as_deref().unwrap_or("<untitled>"),
+            window.id
+        );
+    }
+    eprint!("Continue? [y/N] ");
+    io::stderr().flush().into_diagnostic()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).into_diagnostic()?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
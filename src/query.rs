@@ -0,0 +1,663 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use miette::{Context, IntoDiagnostic, Result, bail};
+use niri_ipc::{Event, Request, Response, Window, socket::Socket};
+
+use crate::{
+    config::{Application, Config, MatchRule, RequireMode},
+    ipc::NiriIpc,
+    matching::{is_window_match_rule, match_application_windows, match_windows},
+    table::{Cell, Color, print_table},
+};
+
+fn format_optional<T: ToString>(value: &Option<T>) -> Cell {
+    let text = value
+        .as_ref()
+        .map(ToString::to_string)
+        .unwrap_or_else(|| "-".to_string());
+
+    Cell::from(text)
+}
+
+fn print_windows(windows: &[&Window], json: bool) -> Result<()> {
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(windows).into_diagnostic()?
+        );
+        return Ok(());
+    }
+
+    let rows = windows
+        .iter()
+        .map(|window| {
+            vec![
+                Cell::from(window.id.to_string()),
+                format_optional(&window.app_id),
+                format_optional(&window.title),
+                format_optional(&window.pid),
+                format_optional(&window.workspace_id),
+                Cell::flag(window.is_focused, Color::Green),
+            ]
+        })
+        .collect::<Vec<_>>();
+    print_table(
+        &["ID", "APP ID", "TITLE", "PID", "WORKSPACE", "FOCUSED"],
+        &rows,
+    );
+
+    Ok(())
+}
+
+pub fn get_sorted_windows(socket: &mut impl NiriIpc) -> Result<Vec<Window>> {
+    let Response::Windows(mut windows) = socket.send(Request::Windows)? else {
+        bail!("Failed to retrieve windows from Niri daemon");
+    };
+    windows.sort_by_key(|window| window.id);
+
+    Ok(windows)
+}
+
+pub fn windows(socket: &mut impl NiriIpc, json: bool) -> Result<()> {
+    let windows = get_sorted_windows(socket)?;
+
+    print_windows(&windows.iter().collect::<Vec<_>>(), json)
+}
+
+fn describe_window(sign: char, window: &Window) -> String {
+    format!(
+        "{sign} {}\t{}\t{}",
+        window.id,
+        window.app_id.as_deref().unwrap_or("-"),
+        window.title.as_deref().unwrap_or("-")
+    )
+}
+
+/// Follows the open windows through Niri's event stream and describes how they change.
+#[derive(Default)]
+struct WindowWatcher {
+    /// `None` until Niri sends the initial window list, which is not reported as a change.
+    windows: Option<BTreeMap<u64, Window>>,
+}
+
+impl WindowWatcher {
+    /// Applies an event and returns a line for each window that appeared (`+`),
+    /// disappeared (`-`) or changed its app ID or title (`~`).
+    fn apply(&mut self, event: Event) -> Vec<String> {
+        match event {
+            Event::WindowsChanged { windows } => {
+                let windows = windows
+                    .into_iter()
+                    .map(|window| (window.id, window))
+                    .collect::<BTreeMap<_, _>>();
+                let changes = self
+                    .windows
+                    .as_ref()
+                    .map(|known| {
+                        let closed = known
+                            .values()
+                            .filter(|window| !windows.contains_key(&window.id))
+                            .map(|window| describe_window('-', window));
+                        let opened = windows
+                            .values()
+                            .filter(|window| !known.contains_key(&window.id))
+                            .map(|window| describe_window('+', window));
+                        closed.chain(opened).collect()
+                    })
+                    .unwrap_or_default();
+                self.windows = Some(windows);
+                changes
+            }
+            Event::WindowOpenedOrChanged { window } => {
+                let Some(known) = &mut self.windows else {
+                    return vec![];
+                };
+                let change = match known.get(&window.id) {
+                    None => Some(describe_window('+', &window)),
+                    Some(previous)
+                        if previous.app_id != window.app_id || previous.title != window.title =>
+                    {
+                        Some(describe_window('~', &window))
+                    }
+                    Some(_) => None,
+                };
+                known.insert(window.id, window);
+                change.into_iter().collect()
+            }
+            Event::WindowClosed { id } => self
+                .windows
+                .as_mut()
+                .and_then(|known| known.remove(&id))
+                .map(|window| describe_window('-', &window))
+                .into_iter()
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+/// Prints the windows that appear, disappear or change their app ID or title, until the
+/// process is interrupted.
+pub fn watch_windows(mut socket: Socket) -> Result<()> {
+    NiriIpc::send(&mut socket, Request::EventStream)?;
+    eprintln!("Watching for window changes, press Ctrl-C to stop.");
+
+    let mut watcher = WindowWatcher::default();
+    let mut read_event = socket.read_events();
+    loop {
+        let event = read_event()
+            .into_diagnostic()
+            .context("Failed to read event from Niri")?;
+        for line in watcher.apply(event) {
+            println!("{line}");
+        }
+    }
+}
+
+/// Prints the open windows that an ad-hoc rule matches.
+pub fn match_rule(socket: &mut impl NiriIpc, rule: MatchRule, json: bool) -> Result<()> {
+    let windows = get_sorted_windows(socket)?;
+    let matched_windows = match_windows(&windows, &[rule], &[]);
+    if matched_windows.is_empty() && !json {
+        eprintln!("No window matches the rule.");
+        return Ok(());
+    }
+
+    print_windows(&matched_windows, json)
+}
+
+/// Returns the names of the applications whose rules target the window with `window_id`.
+/// Rules are evaluated against all open `windows`, so that `index` and `limit` apply.
+fn matching_applications<'a>(
+    config: &'a Config,
+    windows: &[Window],
+    window_id: u64,
+) -> Vec<&'a str> {
+    config
+        .applications
+        .iter()
+        .filter(|application| {
+            match_application_windows(windows, application)
+                .iter()
+                .any(|window| window.id == window_id)
+        })
+        .map(|application| application.name.as_str())
+        .collect()
+}
+
+/// Prints the applications whose rules target the window with `window_id`, to find out
+/// which hotkeys act on it and where rules overlap.
+pub fn which(socket: &mut impl NiriIpc, config: &Config, window_id: u64) -> Result<()> {
+    let windows = get_sorted_windows(socket)?;
+    if !windows.iter().any(|window| window.id == window_id) {
+        bail!(
+            "No open window has the ID {window_id}, run `niri-app-hotkey windows` to list the open windows"
+        );
+    }
+
+    let names = matching_applications(config, &windows, window_id);
+    if names.is_empty() {
+        eprintln!("No application matches the window.");
+        return Ok(());
+    }
+    for name in &names {
+        println!("{name}");
+    }
+    if names.len() > 1 {
+        eprintln!(
+            "Warning: {} applications match the window, their rules overlap",
+            names.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Builds a row for each configured application and, if the open `windows` are known, the
+/// windows it currently targets. With `only_running`, applications without a matched window
+/// are left out, unless the windows are unknown.
+fn application_rows(
+    config: &Config,
+    windows: Option<&[Window]>,
+    only_running: bool,
+) -> Vec<Vec<Cell>> {
+    config
+        .applications
+        .iter()
+        .filter_map(|application| {
+            let spawn = application
+                .spawn
+                .as_ref()
+                .map(|spawn| spawn.join(" "))
+                .or_else(|| application.spawn_sh.clone());
+            let matched_ids = windows.map(|windows| {
+                match_application_windows(windows, application)
+                    .iter()
+                    .map(|window| window.id.to_string())
+                    .collect::<Vec<_>>()
+            });
+            if only_running && matched_ids.as_ref().is_some_and(Vec::is_empty) {
+                return None;
+            }
+            let matched_windows = matched_ids.map(|ids| {
+                if ids.is_empty() {
+                    "-".to_string()
+                } else {
+                    ids.join(",")
+                }
+            });
+            Some(vec![
+                Cell::from(application.name.clone()),
+                format_optional(&spawn),
+                format_optional(&matched_windows),
+                format_optional(&application.description),
+            ])
+        })
+        .collect()
+}
+
+/// Prints the configured applications and, if the open `windows` are known, the windows
+/// each application currently targets. With `only_running`, only applications that target
+/// a window are printed.
+pub fn applications(config: &Config, windows: Option<&[Window]>, only_running: bool) {
+    print_table(
+        &["NAME", "SPAWN", "WINDOWS", "DESCRIPTION"],
+        &application_rows(config, windows, only_running),
+    );
+}
+
+pub fn workspaces(socket: &mut impl NiriIpc, json: bool) -> Result<()> {
+    let Response::Workspaces(mut workspaces) = socket.send(Request::WorkspacesWithHidden)? else {
+        bail!("Failed to retrieve workspaces from Niri daemon");
+    };
+    workspaces.sort_by_key(|workspace| workspace.id);
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&workspaces).into_diagnostic()?
+        );
+        return Ok(());
+    }
+
+    let rows = workspaces
+        .iter()
+        .map(|workspace| {
+            vec![
+                Cell::from(workspace.id.to_string()),
+                Cell::from(workspace.idx.to_string()),
+                format_optional(&workspace.name),
+                format_optional(&workspace.output),
+                Cell::flag(workspace.is_focused, Color::Green),
+                Cell::flag(workspace.is_hidden, Color::Yellow),
+            ]
+        })
+        .collect::<Vec<_>>();
+    print_table(
+        &["ID", "INDEX", "NAME", "OUTPUT", "FOCUSED", "HIDDEN"],
+        &rows,
+    );
+
+    Ok(())
+}
+
+/// Warns about match rules that do not match any currently open window.
+pub fn report_unused_rules(socket: &mut impl NiriIpc, config: &Config) -> Result<()> {
+    let Response::Windows(windows) = socket.send(Request::Windows)? else {
+        bail!("Failed to retrieve windows from Niri daemon");
+    };
+
+    let is_unused = |rule| {
+        !windows
+            .iter()
+            .any(|window| is_window_match_rule(window, rule))
+    };
+
+    for application in &config.applications {
+        for (index, rule) in application.matches.iter().enumerate() {
+            if is_unused(rule) {
+                eprintln!(
+                    "Warning: match rule #{} of application '{}' does not match any open window",
+                    index + 1,
+                    application.name
+                );
+            }
+        }
+        for (group_index, group) in application.groups.iter().enumerate() {
+            for (index, rule) in group.matches.iter().enumerate() {
+                if is_unused(rule) {
+                    eprintln!(
+                        "Warning: match rule #{} of group #{} of application '{}' does not match any open window",
+                        index + 1,
+                        group_index + 1,
+                        application.name
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the match rules of an application, own rules first, each with a label naming it
+/// in messages.
+fn labeled_match_rules(application: &Application) -> Vec<(String, &MatchRule)> {
+    let own_rules = application
+        .matches
+        .iter()
+        .enumerate()
+        .map(|(index, rule)| (format!("match rule #{}", index + 1), rule));
+    let group_rules = application
+        .groups
+        .iter()
+        .enumerate()
+        .flat_map(|(group_index, group)| {
+            group.matches.iter().enumerate().map(move |(index, rule)| {
+                let label = format!("match rule #{} of group #{}", index + 1, group_index + 1);
+                (label, rule)
+            })
+        });
+
+    own_rules.chain(group_rules).collect()
+}
+
+/// Judges from the rules alone whether they could target the same window: they share at
+/// least one property and every property both set has the same value, as with copied rules
+/// or a rule that only narrows down another. Rules that pick different indices or use
+/// `require="any"` are not compared.
+fn rules_may_overlap(a: &MatchRule, b: &MatchRule) -> bool {
+    if a.require == RequireMode::Any || b.require == RequireMode::Any {
+        return false;
+    }
+    if let (Some(a_index), Some(b_index)) = (a.index, b.index)
+        && a_index != b_index
+    {
+        return false;
+    }
+
+    let properties = |rule: &MatchRule| {
+        [
+            rule.id.map(|id| id.to_string()),
+            rule.app_id
+                .as_ref()
+                .map(|app_id| format!("{} {}", app_id.as_str(), rule.case_sensitive)),
+            rule.title.as_ref().map(|title| title.as_str().to_string()),
+            rule.is_focused.map(|is_focused| is_focused.to_string()),
+            rule.is_floating.map(|is_floating| is_floating.to_string()),
+            rule.is_urgent.map(|is_urgent| is_urgent.to_string()),
+        ]
+    };
+    let mut shared = 0;
+    for pair in properties(a).into_iter().zip(properties(b)) {
+        match pair {
+            (Some(a), Some(b)) if a == b => shared += 1,
+            (Some(_), Some(_)) => return false,
+            _ => {}
+        }
+    }
+
+    shared > 0
+}
+
+/// Describes each pair of applications with match rules that may target the same window,
+/// naming the first pair of such rules.
+fn overlapping_rules(config: &Config) -> Vec<String> {
+    let rules = config
+        .applications
+        .iter()
+        .map(|application| (application, labeled_match_rules(application)))
+        .collect::<Vec<_>>();
+
+    let mut overlaps = vec![];
+    for (position, (a, a_rules)) in rules.iter().enumerate() {
+        for (b, b_rules) in &rules[position + 1..] {
+            let overlap = a_rules.iter().find_map(|(a_label, a_rule)| {
+                b_rules
+                    .iter()
+                    .find(|(_, b_rule)| rules_may_overlap(a_rule, b_rule))
+                    .map(|(b_label, _)| (a_label, b_label))
+            });
+            if let Some((a_label, b_label)) = overlap {
+                overlaps.push(format!(
+                    "{a_label} of application '{}' and {b_label} of application '{}' may target the same window",
+                    a.name, b.name
+                ));
+            }
+        }
+    }
+
+    overlaps
+}
+
+/// Warns about applications whose match rules may target the same window, judging by the
+/// rules alone, so that it works without Niri.
+pub fn report_overlapping_rules(config: &Config) {
+    for overlap in overlapping_rules(config) {
+        eprintln!("Warning: {overlap}");
+    }
+}
+
+/// Warns about open windows that the rules of several applications target.
+pub fn report_shared_windows(socket: &mut impl NiriIpc, config: &Config) -> Result<()> {
+    let windows = get_sorted_windows(socket)?;
+    for window in &windows {
+        let names = matching_applications(config, &windows, window.id);
+        if names.len() > 1 {
+            eprintln!(
+                "Warning: window {} ({}) is targeted by applications {}",
+                window.id,
+                window.app_id.as_deref().unwrap_or("-"),
+                names
+                    .iter()
+                    .map(|name| format!("'{name}'"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the minimum, average and maximum of the given durations.
+fn latency_summary(durations: &[Duration]) -> Option<(Duration, Duration, Duration)> {
+    let min = durations.iter().min()?;
+    let max = durations.iter().max()?;
+    let average = durations.iter().sum::<Duration>() / durations.len() as u32;
+
+    Some((*min, average, *max))
+}
+
+/// Sends `count` window queries to Niri one after another and prints how long the round
+/// trips took.
+pub fn ping(socket: &mut impl NiriIpc, count: u32) -> Result<()> {
+    let mut durations = vec![];
+    for _ in 0..count {
+        let start = Instant::now();
+        let Response::Windows(_) = socket.send(Request::Windows)? else {
+            bail!("Failed to retrieve windows from Niri daemon");
+        };
+        durations.push(start.elapsed());
+    }
+
+    if let Some((min, average, max)) = latency_summary(&durations) {
+        println!(
+            "{count} request(s): min/avg/max = {:.3}/{:.3}/{:.3} ms",
+            min.as_secs_f64() * 1000.0,
+            average.as_secs_f64() * 1000.0,
+            max.as_secs_f64() * 1000.0
+        );
+    }
+
+    Ok(())
+}
+
+pub fn niri_version(socket: &mut impl NiriIpc) -> Result<String> {
+    let Response::Version(version) = socket.send(Request::Version)? else {
+        bail!("Failed to retrieve version from Niri daemon");
+    };
+
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipc::fake::window;
+
+    #[test]
+    fn latency_summary_reports_min_average_and_max() {
+        let durations = [10, 30, 20].map(Duration::from_millis);
+
+        assert_eq!(
+            latency_summary(&durations),
+            Some((
+                Duration::from_millis(10),
+                Duration::from_millis(20),
+                Duration::from_millis(30)
+            ))
+        );
+        assert_eq!(latency_summary(&[]), None);
+    }
+
+    #[test]
+    fn window_watcher_reports_changes_after_initial_list() {
+        let mut watcher = WindowWatcher::default();
+        let mut renamed = window(2, "kitty", 1, false);
+        renamed.title = Some("vim".to_string());
+
+        let changes = [
+            Event::WindowsChanged {
+                windows: vec![window(1, "firefox", 1, true), window(2, "kitty", 1, false)],
+            },
+            Event::WindowOpenedOrChanged {
+                window: window(3, "mpv", 1, false),
+            },
+            Event::WindowOpenedOrChanged {
+                window: window(1, "firefox", 1, false),
+            },
+            Event::WindowOpenedOrChanged { window: renamed },
+            Event::WindowClosed { id: 1 },
+            Event::WindowsChanged {
+                windows: vec![window(3, "mpv", 1, false), window(4, "foot", 1, false)],
+            },
+        ]
+        .into_iter()
+        .flat_map(|event| watcher.apply(event))
+        .collect::<Vec<_>>();
+
+        assert_eq!(
+            changes,
+            [
+                "+ 3\tmpv\tmpv window 3",
+                "~ 2\tkitty\tvim",
+                "- 1\tfirefox\tfirefox window 1",
+                "- 2\tkitty\tvim",
+                "+ 4\tfoot\tfoot window 4",
+            ]
+        );
+    }
+
+    #[test]
+    fn matching_applications_lists_every_application_targeting_the_window() {
+        let config = Config::from_str(
+            "config.toml",
+            "[[application]]\nname = \"Terminal\"\nspawn = [\"kitty\"]\n[[application.match]]\napp-id = \"kitty\"\n\n[[application]]\nname = \"Scratchpad\"\nspawn = [\"kitty\"]\n[[application.match]]\napp-id = \"kitty\"\nindex = \"last\"\n\n[[application]]\nname = \"Firefox\"\nspawn = [\"firefox\"]\n[[application.match]]\napp-id = \"firefox\"\n",
+        )
+        .unwrap();
+        let windows = [window(1, "kitty", 1, true), window(2, "kitty", 1, false)];
+
+        assert_eq!(matching_applications(&config, &windows, 1), ["Terminal"]);
+        assert_eq!(
+            matching_applications(&config, &windows, 2),
+            ["Terminal", "Scratchpad"]
+        );
+    }
+
+    #[test]
+    fn overlapping_rules_reports_identical_and_narrowed_rules() {
+        let config = Config::from_str(
+            "config.toml",
+            r#"
+            [[application]]
+            name = "Terminal"
+            spawn = ["kitty"]
+            match = [{ app-id = "kitty" }]
+
+            [[application]]
+            name = "Editor"
+            spawn = ["kitty", "nvim"]
+            match = [{ app-id = "kitty", title = "nvim" }]
+
+            [[application]]
+            name = "Scratchpad"
+            spawn = ["kitty"]
+            match = [{ app-id = "kitty", is-floating = true, index = 1 }]
+
+            [[application]]
+            name = "Second terminal"
+            spawn = ["kitty"]
+            match = [{ app-id = "kitty", index = 0 }]
+
+            [[application]]
+            name = "Firefox"
+            spawn = ["firefox"]
+            match = [{ app-id = "firefox" }]
+            "#,
+        )
+        .unwrap();
+
+        let overlaps = overlapping_rules(&config);
+
+        assert_eq!(
+            overlaps,
+            [
+                "match rule #1 of application 'Terminal' and match rule #1 of application 'Editor' may target the same window",
+                "match rule #1 of application 'Terminal' and match rule #1 of application 'Scratchpad' may target the same window",
+                "match rule #1 of application 'Terminal' and match rule #1 of application 'Second terminal' may target the same window",
+                "match rule #1 of application 'Editor' and match rule #1 of application 'Scratchpad' may target the same window",
+                "match rule #1 of application 'Editor' and match rule #1 of application 'Second terminal' may target the same window",
+            ]
+        );
+    }
+
+    #[test]
+    fn application_rows_show_description() {
+        let config = Config::from_str(
+            "config.toml",
+            "[[application]]\nname = \"Firefox\"\ndescription = \"Personal profile\"\nspawn = [\"firefox\"]\n[[application.match]]\napp-id = \"firefox\"\n\n[[application]]\nname = \"Kitty\"\nspawn = [\"kitty\"]\n",
+        )
+        .unwrap();
+        let windows = [window(1, "firefox", 1, true)];
+
+        let rows = application_rows(&config, Some(&windows), false);
+
+        assert_eq!(
+            rows[0],
+            ["Firefox", "firefox", "1", "Personal profile"]
+                .map(|text| Cell::from(text.to_string()))
+        );
+        assert_eq!(rows[1][3], Cell::from("-".to_string()));
+    }
+
+    #[test]
+    fn application_rows_only_running_skips_applications_without_windows() {
+        let config = Config::from_str(
+            "config.toml",
+            "[[application]]\nname = \"Firefox\"\nspawn = [\"firefox\"]\n[[application.match]]\napp-id = \"firefox\"\n\n[[application]]\nname = \"Kitty\"\nspawn = [\"kitty\"]\n[[application.match]]\napp-id = \"kitty\"\n",
+        )
+        .unwrap();
+        let windows = [window(1, "firefox", 1, true)];
+
+        let rows = application_rows(&config, Some(&windows), true);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0][0], Cell::from("Firefox".to_string()));
+
+        // Without the open windows, every application is listed
+        assert_eq!(application_rows(&config, None, true).len(), 2);
+    }
+}
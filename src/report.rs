@@ -0,0 +1,223 @@
+use miette::Result;
+use niri_ipc::{Action, Request, Response, Window, Workspace, WorkspaceReferenceArg};
+use serde::{Deserialize, Serialize};
+
+use crate::ipc::NiriIpc;
+
+/// A window a command acted on, and a line describing what it did to it.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WindowReport {
+    pub id: u64,
+    pub message: String,
+}
+
+/// What a command did to a single window.
+#[derive(Default)]
+struct WindowOutcome {
+    id: u64,
+    moved_to: Option<WorkspaceReferenceArg>,
+    focused: bool,
+    fullscreen_toggled: bool,
+    closed: bool,
+}
+
+/// Passes requests on to Niri and keeps track of the windows the actions among them acted
+/// on, so that a command can report which windows it affected. Titles and workspace names
+/// are taken from the window and workspace lists the command queried along the way.
+pub struct ReportingSocket<'a, S> {
+    socket: &'a mut S,
+    windows: Vec<Window>,
+    workspaces: Vec<Workspace>,
+    outcomes: Vec<WindowOutcome>,
+}
+
+impl<'a, S: NiriIpc> ReportingSocket<'a, S> {
+    pub fn new(socket: &'a mut S) -> Self {
+        Self {
+            socket,
+            windows: vec![],
+            workspaces: vec![],
+            outcomes: vec![],
+        }
+    }
+
+    /// Reports the windows acted on, in the order they were first acted on.
+    pub fn reports(&self) -> Vec<WindowReport> {
+        self.outcomes
+            .iter()
+            .map(|outcome| WindowReport {
+                id: outcome.id,
+                message: self.describe_outcome(outcome),
+            })
+            .collect()
+    }
+
+    /// Describes what happened to a window, e.g. `Focused window 'Firefox' (id 42) on
+    /// workspace 'web'`.
+    fn describe_outcome(&self, outcome: &WindowOutcome) -> String {
+        let window = self.describe_window(outcome.id);
+        if outcome.closed {
+            return format!("Closed {window}");
+        }
+        if outcome.fullscreen_toggled {
+            return format!("Toggled fullscreen of {window}");
+        }
+
+        match (&outcome.moved_to, outcome.focused) {
+            (Some(workspace), true) => format!(
+                "Moved {window} to {} and focused it",
+                self.describe_workspace(workspace)
+            ),
+            (Some(workspace), false) => {
+                format!("Moved {window} to {}", self.describe_workspace(workspace))
+            }
+            (None, _) => {
+                let workspace = self
+                    .windows
+                    .iter()
+                    .find(|window| window.id == outcome.id)
+                    .and_then(|window| window.workspace_id)
+                    .map(WorkspaceReferenceArg::Id);
+                match workspace {
+                    Some(workspace) => format!(
+                        "Focused {window} on {}",
+                        self.describe_workspace(&workspace)
+                    ),
+                    None => format!("Focused {window}"),
+                }
+            }
+        }
+    }
+
+    fn describe_window(&self, id: u64) -> String {
+        let title = self
+            .windows
+            .iter()
+            .find(|window| window.id == id)
+            .and_then(|window| window.title.as_deref());
+        match title {
+            Some(title) => format!("window '{title}' (id {id})"),
+            None => format!("window {id}"),
+        }
+    }
+
+    fn describe_workspace(&self, reference: &WorkspaceReferenceArg) -> String {
+        let id = match reference {
+            WorkspaceReferenceArg::Id(id) => id,
+            WorkspaceReferenceArg::Index(index) => return format!("workspace {index}"),
+            WorkspaceReferenceArg::Name(name) => return format!("workspace '{name}'"),
+        };
+        match self.workspaces.iter().find(|workspace| workspace.id == *id) {
+            Some(Workspace {
+                name: Some(name), ..
+            }) => format!("workspace '{name}'"),
+            Some(workspace) => format!("workspace {}", workspace.idx),
+            None => format!("workspace with id {id}"),
+        }
+    }
+
+    fn outcome(&mut self, id: u64) -> &mut WindowOutcome {
+        match self.outcomes.iter().position(|outcome| outcome.id == id) {
+            Some(index) => &mut self.outcomes[index],
+            None => {
+                self.outcomes.push(WindowOutcome {
+                    id,
+                    ..Default::default()
+                });
+                self.outcomes.last_mut().unwrap()
+            }
+        }
+    }
+
+    fn record(&mut self, action: &Action) {
+        match action {
+            Action::FocusWindow { id } => self.outcome(*id).focused = true,
+            Action::MoveWindowToWorkspace {
+                window_id: Some(id),
+                reference,
+                focus,
+            } => {
+                let outcome = self.outcome(*id);
+                outcome.moved_to = Some(reference.clone());
+                outcome.focused = *focus;
+            }
+            Action::FullscreenWindow { id: Some(id) } => {
+                self.outcome(*id).fullscreen_toggled = true;
+            }
+            Action::CloseWindow { id: Some(id) } => self.outcome(*id).closed = true,
+            _ => {}
+        }
+    }
+}
+
+impl<S: NiriIpc> NiriIpc for ReportingSocket<'_, S> {
+    fn send(&mut self, request: Request) -> Result<Response> {
+        let action = match &request {
+            Request::Action(action) => Some(action.clone()),
+            _ => None,
+        };
+        let response = self.socket.send(request)?;
+
+        match &response {
+            // Windows missing from later lists, e.g. closed ones, keep their last known title
+            Response::Windows(windows) => {
+                for window in windows {
+                    match self.windows.iter_mut().find(|known| known.id == window.id) {
+                        Some(known) => *known = window.clone(),
+                        None => self.windows.push(window.clone()),
+                    }
+                }
+            }
+            Response::Workspaces(workspaces) => self.workspaces = workspaces.clone(),
+            _ => {}
+        }
+        if let Some(action) = action {
+            self.record(&action);
+        }
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipc::fake::{FakeIpc, window, workspace};
+
+    #[test]
+    fn messages_describe_each_window_acted_on() {
+        let mut named = workspace(2, false, false);
+        named.name = Some("web".to_string());
+        let mut ipc = FakeIpc::new(
+            vec![window(1, "firefox", 1, true), window(2, "kitty", 2, false)],
+            vec![workspace(1, true, false), named, workspace(3, false, true)],
+        );
+        let mut socket = ReportingSocket::new(&mut ipc);
+
+        socket.send(Request::Windows).unwrap();
+        socket.send(Request::WorkspacesWithHidden).unwrap();
+        for action in [
+            Action::MoveWindowToWorkspace {
+                window_id: Some(1),
+                reference: WorkspaceReferenceArg::Id(3),
+                focus: false,
+            },
+            Action::FocusWindow { id: 2 },
+        ] {
+            socket.send(Request::Action(action)).unwrap();
+        }
+
+        let messages = socket
+            .reports()
+            .into_iter()
+            .map(|report| report.message)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "Moved window 'firefox window 1' (id 1) to workspace 3",
+                "Focused window 'kitty window 2' (id 2) on workspace 'web'",
+            ]
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    env,
+    io::{self, IsTerminal},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Color {
+    Green,
+    Yellow,
+}
+
+impl Color {
+    fn code(self) -> &'static str {
+        match self {
+            Self::Green => "32",
+            Self::Yellow => "33",
+        }
+    }
+}
+
+/// A table cell, optionally highlighted when printed to a terminal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cell {
+    text: String,
+    color: Option<Color>,
+}
+
+impl Cell {
+    /// A boolean flag that is highlighted with `color` when set.
+    pub fn flag(value: bool, color: Color) -> Self {
+        Self {
+            text: value.to_string(),
+            color: value.then_some(color),
+        }
+    }
+}
+
+impl From<String> for Cell {
+    fn from(text: String) -> Self {
+        Self { text, color: None }
+    }
+}
+
+/// Follows https://no-color.org: any non-empty `NO_COLOR` disables colors.
+fn is_color_disabled() -> bool {
+    env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+fn paint(text: &str, code: &str) -> String {
+    format!("\x1b[{code}m{text}\x1b[0m")
+}
+
+fn format_row(cells: &[Cell], widths: &[usize], colored: bool) -> String {
+    let last = cells.len().saturating_sub(1);
+    cells
+        .iter()
+        .zip(widths)
+        .enumerate()
+        .map(|(column, (cell, width))| {
+            // Padding the last column would only add trailing whitespace
+            let padding = if column == last {
+                0
+            } else {
+                width.saturating_sub(cell.text.chars().count())
+            };
+            let text = match cell.color {
+                Some(color) if colored => paint(&cell.text, color.code()),
+                _ => cell.text.clone(),
+            };
+            format!("{text}{}", " ".repeat(padding))
+        })
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+/// Prints rows as aligned columns when stdout is a terminal, with flags highlighted unless
+/// `NO_COLOR` is set. Otherwise prints plain tab-separated values, which are easier to
+/// process with other tools.
+pub fn print_table(headers: &[&str], rows: &[Vec<Cell>]) {
+    if !io::stdout().is_terminal() {
+        println!("{}", headers.join("\t"));
+        for row in rows {
+            let cells = row
+                .iter()
+                .map(|cell| cell.text.as_str())
+                .collect::<Vec<_>>();
+            println!("{}", cells.join("\t"));
+        }
+        return;
+    }
+
+    let mut widths = headers
+        .iter()
+        .map(|header| header.len())
+        .collect::<Vec<_>>();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.text.chars().count());
+        }
+    }
+
+    let colored = !is_color_disabled();
+    let header_cells = headers
+        .iter()
+        .map(|header| Cell::from(header.to_string()))
+        .collect::<Vec<_>>();
+    let header = format_row(&header_cells, &widths, colored);
+    if colored {
+        println!("{}", paint(&header, "1"));
+    } else {
+        println!("{header}");
+    }
+    for row in rows {
+        println!("{}", format_row(row, &widths, colored));
+    }
+}